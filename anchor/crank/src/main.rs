@@ -0,0 +1,366 @@
+// Expiry crank for the solmarket escrow program.
+//
+// Scans for SolEscrow accounts that can be expired and sends crank_expire for each,
+// collecting Config.crank_bounty_lamports per escrow.
+//
+//   solmarket-crank [--url URL] [--keypair PATH] [--interval SECS] [--once] [--dry-run]
+
+use std::{env, process, thread, time::Duration};
+
+use anchor_lang::{AccountDeserialize, Discriminator, InstructionData, Space, ToAccountMetas};
+use solana_account_decoder::UiAccountEncoding;
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_rpc_client_api::{
+    config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{
+    account::from_account,
+    clock::Clock,
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair, Signer},
+    system_program,
+    sysvar,
+    transaction::Transaction,
+};
+use solmarket::state::{Config, EscrowStatus, SolEscrow};
+
+// Byte offset of SolEscrow.status: discriminator, order_id, buyer, merchant, amount_lamports, fee_bps
+const STATUS_OFFSET: usize = 8 + 8 + 32 + 32 + 8 + 2;
+
+struct Options {
+    url: String,
+    keypair: String,
+    interval: u64,
+    once: bool,
+    dry_run: bool,
+}
+
+fn parse_options() -> Options {
+    let home = env::var("HOME").unwrap_or_default();
+    let mut options = Options {
+        url: "http://127.0.0.1:8899".to_string(),
+        keypair: format!("{home}/.config/solana/id.json"),
+        interval: 60,
+        once: false,
+        dry_run: false,
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--url" => options.url = args.next().unwrap_or_else(|| usage()),
+            "--keypair" => options.keypair = args.next().unwrap_or_else(|| usage()),
+            "--interval" => {
+                options.interval = args.next().and_then(|s| s.parse().ok()).unwrap_or_else(|| usage())
+            }
+            "--once" => options.once = true,
+            "--dry-run" => options.dry_run = true,
+            _ => usage(),
+        }
+    }
+    options
+}
+
+fn usage() -> ! {
+    eprintln!("usage: solmarket-crank [--url URL] [--keypair PATH] [--interval SECS] [--once] [--dry-run]");
+    process::exit(2);
+}
+
+// Current-layout escrows with the given status. Older layouts have a different size
+// and are skipped until someone runs migrate_escrow on them.
+fn escrows_with_status(client: &RpcClient, status: EscrowStatus) -> Result<Vec<(Pubkey, SolEscrow)>, String> {
+    let config = RpcProgramAccountsConfig {
+        filters: Some(escrow_filters(status)),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            ..RpcAccountInfoConfig::default()
+        },
+        ..RpcProgramAccountsConfig::default()
+    };
+    let accounts = client
+        .get_program_accounts_with_config(&solmarket::ID, config)
+        .map_err(|e| e.to_string())?;
+    Ok(accounts
+        .into_iter()
+        .filter_map(|(key, account)| {
+            SolEscrow::try_deserialize(&mut account.data.as_slice()).ok().map(|escrow| (key, escrow))
+        })
+        .collect())
+}
+
+fn escrow_filters(status: EscrowStatus) -> Vec<RpcFilterType> {
+    vec![
+        RpcFilterType::DataSize((SolEscrow::DISCRIMINATOR.len() + SolEscrow::INIT_SPACE) as u64),
+        RpcFilterType::Memcmp(Memcmp::new_base58_encoded(0, &SolEscrow::DISCRIMINATOR)),
+        RpcFilterType::Memcmp(Memcmp::new_base58_encoded(STATUS_OFFSET, &[status as u8])),
+    ]
+}
+
+// Same rules as the program: Pending escrows expire at expires_at, Funded ones once
+// the release grace window has closed
+fn is_expired(escrow: &SolEscrow, config: &Config, now: i64) -> bool {
+    if escrow.status == EscrowStatus::Funded as u8 {
+        now >= escrow.expires_at.saturating_add(config.expiry_grace_secs)
+    } else {
+        escrow.status == EscrowStatus::Pending as u8 && now >= escrow.expires_at
+    }
+}
+
+fn crank_expire_ix(cranker: &Pubkey, escrow_key: &Pubkey, escrow: &SolEscrow) -> Instruction {
+    let (config, _) = Pubkey::find_program_address(&[b"config"], &solmarket::ID);
+    let (event_authority, _) = Pubkey::find_program_address(&[b"__event_authority"], &solmarket::ID);
+    // Escrows counted in the buyer's exposure must settle it as they close
+    let buyer_stats = escrow
+        .tracked_in_buyer_stats
+        .then(|| Pubkey::find_program_address(&[b"buyer-stats", escrow.buyer.as_ref()], &solmarket::ID).0);
+    let accounts = solmarket::accounts::CrankExpire {
+        cranker: *cranker,
+        system_program: system_program::ID,
+        config,
+        buyer_account: escrow.buyer,
+        payer: escrow.payer,
+        buyer_stats,
+        escrow: *escrow_key,
+        event_authority,
+        program: solmarket::ID,
+    };
+    Instruction {
+        program_id: solmarket::ID,
+        accounts: accounts.to_account_metas(None),
+        data: solmarket::instruction::CrankExpire {}.data(),
+    }
+}
+
+fn run_once(client: &RpcClient, cranker: &Keypair, dry_run: bool) -> Result<(), String> {
+    let (config_key, _) = Pubkey::find_program_address(&[b"config"], &solmarket::ID);
+    let config_account = client.get_account(&config_key).map_err(|e| e.to_string())?;
+    let config = Config::try_deserialize(&mut config_account.data.as_slice()).map_err(|e| e.to_string())?;
+    if config.paused & solmarket::state::PAUSE_REFUND != 0 {
+        println!("refunds are paused, nothing to do");
+        return Ok(());
+    }
+    // Use cluster time rather than the local clock
+    let clock_account = client.get_account(&sysvar::clock::ID).map_err(|e| e.to_string())?;
+    let clock: Clock = from_account(&clock_account).ok_or("could not decode the clock sysvar")?;
+
+    let mut candidates = escrows_with_status(client, EscrowStatus::Pending)?;
+    candidates.extend(escrows_with_status(client, EscrowStatus::Funded)?);
+    let expired: Vec<_> = candidates
+        .into_iter()
+        .filter(|(_, escrow)| is_expired(escrow, &config, clock.unix_timestamp))
+        .collect();
+    println!("{} expired escrow(s)", expired.len());
+
+    for (key, escrow) in expired {
+        if dry_run {
+            println!("would crank {key} (order {})", escrow.order_id);
+            continue;
+        }
+        let ix = crank_expire_ix(&cranker.pubkey(), &key, &escrow);
+        let blockhash = client.get_latest_blockhash().map_err(|e| e.to_string())?;
+        let tx = Transaction::new_signed_with_payer(&[ix], Some(&cranker.pubkey()), &[cranker], blockhash);
+        // One failure, e.g. someone else cranking first, should not stop the rest
+        match client.send_and_confirm_transaction(&tx) {
+            Ok(signature) => println!("cranked {key} (order {}): {signature}", escrow.order_id),
+            Err(e) => eprintln!("failed to crank {key}: {e}"),
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    let options = parse_options();
+    let cranker = read_keypair_file(&options.keypair).unwrap_or_else(|e| {
+        eprintln!("could not read keypair {}: {e}", options.keypair);
+        process::exit(1);
+    });
+    let client = RpcClient::new_with_commitment(options.url.clone(), CommitmentConfig::confirmed());
+
+    loop {
+        if let Err(e) = run_once(&client, &cranker, options.dry_run) {
+            eprintln!("crank pass failed: {e}");
+        }
+        if options.once {
+            break;
+        }
+        thread::sleep(Duration::from_secs(options.interval));
+    }
+}
+
+// The program's own in-memory runtime, so the tests below run crank_expire for real
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::program_error::ProgramError;
+    use solana_sdk::account::{Account, AccountSharedData};
+    use solmarket::handlers::EscrowError;
+    use solmarket_test_runtime::market::{Market, DAY};
+    use solmarket_test_runtime::{instruction, program_error};
+
+    const GRACE: i64 = DAY;
+    const AMOUNT: u64 = 1_000_000_000;
+
+    // A Pending escrow (order 1) and a Funded one (order 2), both expiring in a day,
+    // with the release grace window open for another day after that
+    fn setup() -> (Market, i64) {
+        let mut m = Market::new();
+        let ix = instruction(
+            solmarket::accounts::UpdateConfig { authority: m.authority, config: m.config },
+            solmarket::instruction::SetExpiryGrace { expiry_grace_secs: GRACE },
+        );
+        m.bank.process(&[ix], &[&m.authority]).unwrap();
+        let expires_at = m.bank.now() + DAY;
+        let ix = m.initialize_escrow(1, AMOUNT, expires_at);
+        m.bank.process(&[ix], &[&m.buyer]).unwrap();
+        m.open_escrow(2, AMOUNT);
+        (m, expires_at)
+    }
+
+    fn escrow(m: &Market, order_id: u64) -> SolEscrow {
+        m.bank.anchor_account::<SolEscrow>(&m.escrow_address(order_id))
+    }
+
+    // In discriminant order
+    fn statuses() -> [EscrowStatus; 10] {
+        [
+            EscrowStatus::Pending,
+            EscrowStatus::Funded,
+            EscrowStatus::Released,
+            EscrowStatus::Refunded,
+            EscrowStatus::Expired,
+            EscrowStatus::Disputed,
+            EscrowStatus::Resolved,
+            EscrowStatus::Shipped,
+            EscrowStatus::Declined,
+            EscrowStatus::Cancelled,
+        ]
+    }
+
+    // What a validator returns for getProgramAccounts with the crank's filters: it
+    // rejects filters it cannot parse, then keeps the accounts that pass all of them
+    fn matching(m: &Market, status: EscrowStatus) -> Vec<Pubkey> {
+        let filters = escrow_filters(status);
+        assert!(filters.iter().all(|filter| filter.verify().is_ok()));
+        m.bank
+            .program_accounts(&solmarket::ID)
+            .into_iter()
+            .filter(|(_, account)| {
+                let account = AccountSharedData::from(Account {
+                    lamports: account.lamports,
+                    data: account.data.clone(),
+                    owner: account.owner,
+                    executable: account.executable,
+                    rent_epoch: u64::MAX,
+                });
+                filters.iter().all(|filter| filter.allows(&account))
+            })
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    fn config(m: &Market) -> Config {
+        m.bank.anchor_account::<Config>(&m.config)
+    }
+
+    fn crank(m: &mut Market, cranker: &Pubkey, order_id: u64) -> Result<(), ProgramError> {
+        let key = m.escrow_address(order_id);
+        let ix = crank_expire_ix(cranker, &key, &escrow(m, order_id));
+        m.bank.process(&[ix], &[cranker])
+    }
+
+    // A cranker already holds enough to pay its transaction fees
+    fn cranker(m: &mut Market) -> Pubkey {
+        let cranker = Pubkey::new_unique();
+        m.bank.airdrop(&cranker, AMOUNT);
+        cranker
+    }
+
+    #[test]
+    fn nothing_is_expired_before_expires_at() {
+        let (mut m, expires_at) = setup();
+        m.bank.warp_to(expires_at - 1);
+        let cranker = cranker(&mut m);
+        for order_id in [1, 2] {
+            assert!(!is_expired(&escrow(&m, order_id), &config(&m), m.bank.now()));
+            let err = crank(&mut m, &cranker, order_id).unwrap_err();
+            assert_eq!(err, program_error(EscrowError::NotExpired));
+        }
+    }
+
+    #[test]
+    fn a_pending_escrow_is_cranked_at_expires_at() {
+        let (mut m, expires_at) = setup();
+        m.bank.warp_to(expires_at);
+        let escrow_key = m.escrow_address(1);
+        assert!(is_expired(&escrow(&m, 1), &config(&m), m.bank.now()));
+
+        let cranker = cranker(&mut m);
+        let bounty = config(&m).crank_bounty_lamports;
+        let rent = m.bank.lamports(&escrow_key);
+        let buyer_before = m.bank.lamports(&m.buyer);
+        crank(&mut m, &cranker, 1).unwrap();
+
+        assert!(m.bank.account(&escrow_key).is_none());
+        assert_eq!(m.bank.lamports(&cranker), AMOUNT + bounty);
+        assert_eq!(m.bank.lamports(&m.buyer), buyer_before + rent - bounty);
+    }
+
+    #[test]
+    fn a_funded_escrow_waits_out_the_grace_window() {
+        let (mut m, expires_at) = setup();
+        let escrow_key = m.escrow_address(2);
+        let cranker = cranker(&mut m);
+
+        // Still releasable to the merchant, so the crank leaves it alone
+        m.bank.warp_to(expires_at);
+        assert!(!is_expired(&escrow(&m, 2), &config(&m), m.bank.now()));
+        let err = crank(&mut m, &cranker, 2).unwrap_err();
+        assert_eq!(err, program_error(EscrowError::NotExpired));
+
+        m.bank.warp_to(expires_at + GRACE);
+        assert!(is_expired(&escrow(&m, 2), &config(&m), m.bank.now()));
+        let bounty = config(&m).crank_bounty_lamports;
+        let held = m.bank.lamports(&escrow_key);
+        let buyer_before = m.bank.lamports(&m.buyer);
+        crank(&mut m, &cranker, 2).unwrap();
+
+        // The buyer gets the order amount and the rent, less the bounty
+        assert!(m.bank.account(&escrow_key).is_none());
+        assert_eq!(m.bank.lamports(&cranker), AMOUNT + bounty);
+        assert_eq!(m.bank.lamports(&m.buyer), buyer_before + held - bounty);
+    }
+
+    #[test]
+    fn the_filters_find_only_expired_pending_and_funded_escrows() {
+        let (mut m, expires_at) = setup();
+        let template = escrow(&m, 2);
+        let space = m.bank.account(&m.escrow_address(2)).unwrap().data.len();
+        // One escrow of every status expiring with orders 1 and 2, and one a week later
+        for code in 0..statuses().len() as u64 {
+            for (order_id, expires_at) in [(10 + code, expires_at), (20 + code, expires_at + 7 * DAY)] {
+                let escrow = SolEscrow { order_id, status: code as u8, expires_at, ..template.clone() };
+                m.bank.set_anchor_account(m.escrow_address(order_id), &escrow, space);
+            }
+        }
+        m.bank.warp_to(expires_at + GRACE);
+
+        let config = config(&m);
+        let mut expired = Vec::new();
+        for (code, status) in statuses().into_iter().enumerate() {
+            let found = matching(&m, status);
+            // Config, the treasury and the merchant are program accounts too, and never match
+            assert_eq!(found.len(), if code < 2 { 3 } else { 2 });
+            for key in found {
+                let escrow = m.bank.anchor_account::<SolEscrow>(&key);
+                assert_eq!(escrow.status as usize, code);
+                if is_expired(&escrow, &config, m.bank.now()) {
+                    expired.push(escrow.order_id);
+                }
+            }
+        }
+        expired.sort();
+        assert_eq!(expired, [1, 2, 10, 11]);
+    }
+}
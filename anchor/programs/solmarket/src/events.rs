@@ -0,0 +1,269 @@
+use anchor_lang::prelude::*;
+
+// Emitted through emit_cpi! so they survive log truncation; every event carries
+// order_id, buyer and merchant so off-chain indexers can key on the order.
+
+#[event]
+pub struct EscrowCreated {
+    pub order_id: u64,
+    pub buyer: Pubkey,
+    pub merchant: Pubkey,
+    pub amount_lamports: u64,
+    pub fee_bps: u16,
+    pub created_at: i64,
+    pub expires_at: i64,
+    pub intent_nonce: u64,
+}
+
+#[event]
+pub struct EscrowFunded {
+    pub order_id: u64,
+    pub buyer: Pubkey,
+    pub merchant: Pubkey,
+    pub amount_lamports: u64,
+    pub funded_at: i64,
+}
+
+#[event]
+pub struct EscrowSecretRevealed {
+    pub order_id: u64,
+    pub buyer: Pubkey,
+    pub merchant: Pubkey,
+    pub secret: Vec<u8>,
+    pub revealed_at: i64,
+}
+
+#[event]
+pub struct EscrowDeliveryAttested {
+    pub order_id: u64,
+    pub buyer: Pubkey,
+    pub merchant: Pubkey,
+    pub attester: Pubkey,
+    pub attested_at: i64,
+}
+
+#[event]
+pub struct ReferralPaid {
+    pub order_id: u64,
+    pub referrer: Pubkey,
+    pub fee: u64,          // full fee charged on the release
+    pub platform_fee: u64, // rest of the fee, accrued in the treasury until withdraw_fees
+    pub referral_fee: u64, // accrued in the Referrer account until claim_referral_fees
+}
+
+#[event]
+pub struct EscrowShipped {
+    pub order_id: u64,
+    pub buyer: Pubkey,
+    pub merchant: Pubkey,
+    pub fulfilment_hash: [u8; 32],
+    pub shipped_at: i64,
+}
+
+#[event]
+pub struct EscrowReleased {
+    pub order_id: u64,
+    pub buyer: Pubkey,
+    pub merchant: Pubkey,
+    pub merchant_payout: u64,
+    pub fee: u64,
+    pub fully_released: bool, // false for an intermediate milestone
+    pub released_at: i64,
+}
+
+#[event]
+pub struct EscrowRefunded {
+    pub order_id: u64,
+    pub buyer: Pubkey,
+    pub merchant: Pubkey,
+    pub refunded_lamports: u64,
+    pub partial: bool,
+    pub refunded_at: i64,
+}
+
+#[event]
+pub struct EscrowDeclined {
+    pub order_id: u64,
+    pub buyer: Pubkey,
+    pub merchant: Pubkey,
+    pub reason_code: u8,
+    pub refunded_lamports: u64,
+    pub declined_at: i64,
+}
+
+#[event]
+pub struct EscrowCancelled {
+    pub order_id: u64,
+    pub buyer: Pubkey,
+    pub merchant: Pubkey,
+    pub refunded_lamports: u64,
+    pub previous_status: u8,
+    pub cancelled_at: i64,
+}
+
+#[event]
+pub struct EscrowExpiryExtended {
+    pub order_id: u64,
+    pub buyer: Pubkey,
+    pub merchant: Pubkey,
+    pub requested_by: Pubkey,
+    pub old_expires_at: i64,
+    pub new_expires_at: i64,
+}
+
+#[event]
+pub struct EscrowExpired {
+    pub order_id: u64,
+    pub buyer: Pubkey,
+    pub merchant: Pubkey,
+    pub refunded_lamports: u64, // 0 when the escrow was never funded
+    pub was_funded: bool,
+    pub status: u8,
+    pub expired_at: i64,
+}
+
+#[event]
+pub struct CrankRewarded {
+    pub order_id: u64,
+    pub cranker: Pubkey,
+    pub bounty: u64,
+}
+
+#[event]
+pub struct EscrowDisputed {
+    pub order_id: u64,
+    pub buyer: Pubkey,
+    pub merchant: Pubkey,
+    pub raised_by: Pubkey,
+    pub disputed_lamports: u64,
+    pub raised_at: i64,
+}
+
+#[event]
+pub struct EscrowDisputeResolved {
+    pub order_id: u64,
+    pub buyer: Pubkey,
+    pub merchant: Pubkey,
+    pub arbiter: Pubkey,
+    pub buyer_bps: u16,
+    pub buyer_refund: u64,
+    pub merchant_payout: u64,
+    pub fee: u64,
+    pub resolved_at: i64,
+}
+
+// One per escrow in batch_release / batch_refund
+#[event]
+pub struct BatchEscrowSettled {
+    pub escrow: Pubkey,
+    pub order_id: u64,
+    pub release: bool, // false for batch_refund
+    pub settled: bool, // false when the escrow was skipped
+    pub merchant_payout: u64,
+    pub fee: u64,
+    pub refunded_lamports: u64,
+}
+
+#[event]
+pub struct CartCreated {
+    pub cart_id: u64,
+    pub buyer: Pubkey,
+    pub total_lamports: u64,
+    pub item_count: u8,
+    pub created_at: i64,
+    pub expires_at: i64,
+}
+
+#[event]
+pub struct CartFunded {
+    pub cart_id: u64,
+    pub buyer: Pubkey,
+    pub total_lamports: u64,
+    pub funded_at: i64,
+}
+
+#[event]
+pub struct CartItemSettled {
+    pub cart_id: u64,
+    pub buyer: Pubkey,
+    pub merchant: Pubkey,
+    pub index: u8,
+    pub status: u8, // Released or Refunded
+    pub merchant_payout: u64,
+    pub fee: u64,
+    pub refunded_lamports: u64,
+    pub settled_at: i64,
+}
+
+#[event]
+pub struct SubscriptionCreated {
+    pub subscription_id: u64,
+    pub buyer: Pubkey,
+    pub merchant: Pubkey,
+    pub period_secs: i64,
+    pub amount_per_period: u64,
+    pub first_charge_at: i64,
+    pub prepaid_lamports: u64,
+}
+
+#[event]
+pub struct SubscriptionFunded {
+    pub subscription_id: u64,
+    pub buyer: Pubkey,
+    pub amount: u64,
+    pub prepaid_lamports: u64, // balance after funding
+}
+
+#[event]
+pub struct SubscriptionCharged {
+    pub subscription_id: u64,
+    pub buyer: Pubkey,
+    pub merchant: Pubkey,
+    pub period_start: i64,
+    pub merchant_payout: u64,
+    pub fee: u64,
+    pub prepaid_lamports: u64, // balance left after the charge
+}
+
+#[event]
+pub struct SubscriptionCancelled {
+    pub subscription_id: u64,
+    pub buyer: Pubkey,
+    pub merchant: Pubkey,
+    pub periods_charged: u32,
+    pub refunded_lamports: u64,
+    pub deferred_lamports: u64, // started periods left for charge_period while releases are paused
+    pub cancelled_at: i64,
+}
+
+#[event]
+pub struct BondPosted {
+    pub merchant: Pubkey,
+    pub lamports: u64,
+    pub tokens: u64,
+    pub posted_at: i64,
+}
+
+#[event]
+pub struct BondWithdrawalRequested {
+    pub merchant: Pubkey,
+    pub lamports: u64,
+    pub tokens: u64,
+    pub available_at: i64,
+}
+
+#[event]
+pub struct BondWithdrawn {
+    pub merchant: Pubkey,
+    pub lamports: u64,
+    pub tokens: u64,
+}
+
+#[event]
+pub struct BondSlashed {
+    pub merchant: Pubkey,
+    pub recipient: Pubkey,
+    pub lamports: u64,
+    pub tokens: u64,
+    pub slashed_at: i64,
+}
@@ -0,0 +1,194 @@
+use anchor_lang::prelude::*;
+use crate::state::{Config, FeeSplit, MAX_FEE_SPLITS};
+use super::sol_escrow::EscrowError;
+
+pub const MAX_FEE_BPS: u16 = 1_000; // 10%
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = authority @ EscrowError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
+
+pub fn update_fee(ctx: Context<UpdateConfig>, fee_bps: u16) -> Result<()> {
+    require!(fee_bps <= MAX_FEE_BPS, EscrowError::FeeTooHigh);
+    ctx.accounts.config.fee_bps = fee_bps;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetFeeRecipient<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = authority @ EscrowError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    /// CHECK: new fee recipient wallet
+    pub fee_recipient: UncheckedAccount<'info>,
+}
+
+pub fn set_fee_recipient(ctx: Context<SetFeeRecipient>) -> Result<()> {
+    ctx.accounts.config.fee_recipient = ctx.accounts.fee_recipient.key();
+    Ok(())
+}
+
+pub fn set_arbiter(ctx: Context<UpdateConfig>, arbiter: Pubkey) -> Result<()> {
+    ctx.accounts.config.arbiter = arbiter;
+    Ok(())
+}
+
+pub fn set_expiry_grace(ctx: Context<UpdateConfig>, expiry_grace_secs: i64) -> Result<()> {
+    require!(expiry_grace_secs >= 0, EscrowError::InvalidExpiry);
+    ctx.accounts.config.expiry_grace_secs = expiry_grace_secs;
+    Ok(())
+}
+
+pub fn set_review_period(ctx: Context<UpdateConfig>, review_period_secs: i64) -> Result<()> {
+    require!(review_period_secs >= 0, EscrowError::InvalidExpiry);
+    ctx.accounts.config.review_period_secs = review_period_secs;
+    Ok(())
+}
+
+pub fn set_max_escrow_duration(ctx: Context<UpdateConfig>, max_escrow_duration_secs: i64) -> Result<()> {
+    require!(max_escrow_duration_secs > 0, EscrowError::InvalidExpiry);
+    ctx.accounts.config.max_escrow_duration_secs = max_escrow_duration_secs;
+    Ok(())
+}
+
+// Require order intents signed by this key on escrow creation; default turns the check off
+pub fn set_order_signer(ctx: Context<UpdateConfig>, order_signer: Pubkey) -> Result<()> {
+    ctx.accounts.config.order_signer = order_signer;
+    Ok(())
+}
+
+// Cap on the share of the fee an escrow may promise its referrer, in bps of the fee
+pub fn set_max_referral_bps(ctx: Context<UpdateConfig>, max_referral_bps: u16) -> Result<()> {
+    require!(max_referral_bps <= 10_000, EscrowError::InvalidBps);
+    ctx.accounts.config.max_referral_bps = max_referral_bps;
+    Ok(())
+}
+
+// Replace the fee splits applied at withdrawal; an empty list withdraws to a single destination
+pub fn set_fee_splits(ctx: Context<UpdateConfig>, splits: Vec<FeeSplit>) -> Result<()> {
+    require!(splits.len() <= MAX_FEE_SPLITS, EscrowError::InvalidFeeSplits);
+    require!(splits.iter().all(|s| s.weight_bps > 0), EscrowError::InvalidFeeSplits);
+    if !splits.is_empty() {
+        let total: u32 = splits.iter().map(|s| s.weight_bps as u32).sum();
+        require!(total == 10_000, EscrowError::InvalidFeeSplits);
+    }
+    let config = &mut ctx.accounts.config;
+    config.fee_splits = [FeeSplit::default(); MAX_FEE_SPLITS];
+    config.fee_splits[..splits.len()].copy_from_slice(&splits);
+    config.fee_split_count = splits.len() as u8;
+    Ok(())
+}
+
+// Token merchants may bond in besides lamports; default disables token bonds.
+// Tokens already posted in an older mint stay withdrawable but stop counting as collateral.
+pub fn set_bond_mint(ctx: Context<UpdateConfig>, bond_mint: Pubkey) -> Result<()> {
+    ctx.accounts.config.bond_mint = bond_mint;
+    Ok(())
+}
+
+pub fn set_bond_token_rate(ctx: Context<UpdateConfig>, bond_token_rate: u64) -> Result<()> {
+    ctx.accounts.config.bond_token_rate = bond_token_rate;
+    Ok(())
+}
+
+pub fn set_min_bond_bps(ctx: Context<UpdateConfig>, min_bond_bps: u16) -> Result<()> {
+    require!(min_bond_bps <= 10_000, EscrowError::InvalidBps);
+    ctx.accounts.config.min_bond_bps = min_bond_bps;
+    Ok(())
+}
+
+pub fn set_bond_withdraw_delay(ctx: Context<UpdateConfig>, bond_withdraw_delay_secs: i64) -> Result<()> {
+    require!(bond_withdraw_delay_secs >= 0, EscrowError::InvalidExpiry);
+    ctx.accounts.config.bond_withdraw_delay_secs = bond_withdraw_delay_secs;
+    Ok(())
+}
+
+// Key whose Ed25519 delivery attestations release escrows; default turns attested release off
+pub fn set_attester(ctx: Context<UpdateConfig>, attester: Pubkey) -> Result<()> {
+    ctx.accounts.config.attester = attester;
+    Ok(())
+}
+
+// Reward for cranking an expired escrow, paid out of its rent
+pub fn set_crank_bounty(ctx: Context<UpdateConfig>, crank_bounty_lamports: u64) -> Result<()> {
+    ctx.accounts.config.crank_bounty_lamports = crank_bounty_lamports;
+    Ok(())
+}
+
+// Bounds on a SOL escrow's amount; a max of 0 means no upper bound
+pub fn set_order_limits(ctx: Context<UpdateConfig>, min_order_lamports: u64, max_order_lamports: u64) -> Result<()> {
+    require!(
+        max_order_lamports == 0 || min_order_lamports <= max_order_lamports,
+        EscrowError::InvalidAmount
+    );
+    let config = &mut ctx.accounts.config;
+    config.min_order_lamports = min_order_lamports;
+    config.max_order_lamports = max_order_lamports;
+    Ok(())
+}
+
+// Per-buyer ceilings on open escrows and their total amount; 0 turns a cap off.
+// With either set, funding an escrow requires the buyer's BuyerStats.
+pub fn set_buyer_caps(
+    ctx: Context<UpdateConfig>,
+    max_open_escrows_per_buyer: u32,
+    max_buyer_exposure_lamports: u64,
+) -> Result<()> {
+    let config = &mut ctx.accounts.config;
+    config.max_open_escrows_per_buyer = max_open_escrows_per_buyer;
+    config.max_buyer_exposure_lamports = max_buyer_exposure_lamports;
+    Ok(())
+}
+
+// Restrict new orders to merchants the authority has verified; existing escrows settle as before
+pub fn set_require_verified_merchants(ctx: Context<UpdateConfig>, require_verified_merchants: bool) -> Result<()> {
+    ctx.accounts.config.require_verified_merchants = require_verified_merchants;
+    Ok(())
+}
+
+// Toggle PAUSE_* bits, e.g. PAUSE_WITHDRAW_ONLY during an incident
+pub fn set_paused(ctx: Context<UpdateConfig>, paused: u8) -> Result<()> {
+    ctx.accounts.config.paused = paused;
+    Ok(())
+}
+
+// Step one of the authority handover: the current authority nominates a successor
+pub fn propose_authority(ctx: Context<UpdateConfig>, new_authority: Pubkey) -> Result<()> {
+    ctx.accounts.config.pending_authority = new_authority;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    pub new_authority: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+}
+
+// Step two: the nominated key signs to take over
+pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+    let config = &mut ctx.accounts.config;
+    let pending = config.pending_authority;
+    require!(pending != Pubkey::default(), EscrowError::NoPendingAuthority);
+    require_keys_eq!(pending, ctx.accounts.new_authority.key(), EscrowError::Unauthorized);
+    config.authority = pending;
+    config.pending_authority = Pubkey::default();
+    Ok(())
+}
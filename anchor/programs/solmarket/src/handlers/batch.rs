@@ -0,0 +1,189 @@
+use anchor_lang::prelude::*;
+use crate::events::BatchEscrowSettled;
+use crate::state::{BuyerStats, Config, EscrowStatus, Merchant, Referrer, SolEscrow, Treasury, PAUSE_REFUND, PAUSE_RELEASE};
+use super::shared::compute_fee;
+use super::sol_escrow::{pay_fee, pay_from_escrow, refund_allowed, release_allowed, settle_exposure, EscrowError};
+
+// Accounts per escrow in remaining_accounts
+// Optional accounts are passed as the program id when absent
+const RELEASE_GROUP: usize = 5; // escrow, merchant PDA, payout wallet, referrer, buyer stats
+const REFUND_GROUP: usize = 3; // escrow, buyer, buyer stats
+
+// Load an escrow from remaining_accounts and check it sits at its own PDA
+fn load_escrow<'info>(info: &'info AccountInfo<'info>) -> Result<Account<'info, SolEscrow>> {
+    let escrow = Account::<SolEscrow>::try_from(info)?;
+    let expected = Pubkey::create_program_address(
+        &[b"sol-escrow", escrow.buyer.as_ref(), &escrow.order_id.to_le_bytes(), &[escrow.bump]],
+        &crate::ID,
+    )
+    .map_err(|_| EscrowError::InvalidBatch)?;
+    require_keys_eq!(info.key(), expected, EscrowError::InvalidBatch);
+    Ok(escrow)
+}
+
+// Load the escrow buyer's stats from remaining_accounts, if passed
+fn load_buyer_stats<'info>(
+    info: &'info AccountInfo<'info>,
+    escrow: &SolEscrow,
+) -> Result<Option<Account<'info, BuyerStats>>> {
+    if info.key() == crate::ID {
+        return Ok(None);
+    }
+    let stats = Account::<BuyerStats>::try_from(info)?;
+    let expected = Pubkey::create_program_address(&[b"buyer-stats", escrow.buyer.as_ref(), &[stats.bump]], &crate::ID)
+        .map_err(|_| EscrowError::InvalidBatch)?;
+    require_keys_eq!(info.key(), expected, EscrowError::InvalidBatch);
+    Ok(Some(stats))
+}
+
+// Load the escrow's referrer from remaining_accounts, if passed
+fn load_referrer<'info>(
+    info: &'info AccountInfo<'info>,
+    escrow: &SolEscrow,
+) -> Result<Option<Account<'info, Referrer>>> {
+    if info.key() == crate::ID {
+        return Ok(None);
+    }
+    let referrer = Account::<Referrer>::try_from(info)?;
+    let expected = Pubkey::create_program_address(&[b"referrer", escrow.referrer.as_ref(), &[referrer.bump]], &crate::ID)
+        .map_err(|_| EscrowError::InvalidBatch)?;
+    require_keys_eq!(info.key(), expected, EscrowError::InvalidBatch);
+    Ok(Some(referrer))
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct BatchRelease<'info> {
+    /// Each escrow is released only if this signer could release it on its own
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump = treasury.bump
+    )]
+    pub treasury: Account<'info, Treasury>,
+}
+
+// remaining_accounts: one RELEASE_GROUP per escrow. Escrows that are not releasable
+// right now are skipped and reported, not failed.
+pub fn batch_release<'info>(ctx: Context<'_, '_, 'info, 'info, BatchRelease<'info>>) -> Result<()> {
+    require!(!ctx.accounts.config.is_paused(PAUSE_RELEASE), EscrowError::Paused);
+    let groups = ctx.remaining_accounts.chunks_exact(RELEASE_GROUP);
+    require!(groups.remainder().is_empty(), EscrowError::InvalidBatch);
+    let now = Clock::get()?.unix_timestamp;
+    let signer = ctx.accounts.authority.key();
+
+    for group in groups {
+        let mut escrow = load_escrow(&group[0])?;
+        let merchant_account = Account::<Merchant>::try_from(&group[1])?;
+        // The stored bump saves a find_program_address per escrow
+        let expected = Pubkey::create_program_address(
+            &[b"merchant", escrow.merchant.as_ref(), &[merchant_account.bump]],
+            &crate::ID,
+        )
+        .map_err(|_| EscrowError::MerchantNotRegistered)?;
+        require_keys_eq!(group[1].key(), expected, EscrowError::MerchantNotRegistered);
+        let payout_wallet = &group[2];
+        require_keys_eq!(payout_wallet.key(), merchant_account.payout_wallet, EscrowError::InvalidBatch);
+        let mut referrer = load_referrer(&group[3], &escrow)?;
+        let mut buyer_stats = load_buyer_stats(&group[4], &escrow)?;
+
+        let status = escrow.status;
+        let releasable = (status == EscrowStatus::Funded as u8 || status == EscrowStatus::Shipped as u8)
+            && release_allowed(&escrow, &ctx.accounts.config, signer, now);
+        let (mut merchant_payout, mut fee) = (0, 0);
+        if releasable {
+            let total = escrow.remaining_lamports();
+            fee = compute_fee(total, escrow.fee_bps);
+            merchant_payout = total - fee;
+            pay_from_escrow(&escrow, payout_wallet, merchant_payout)?;
+            let (platform_fee, _) = pay_fee(&escrow, escrow.referral_bps, &ctx.accounts.treasury, &mut referrer, fee)?;
+            ctx.accounts.treasury.record_release(total, platform_fee, true);
+            settle_exposure(&escrow, &mut buyer_stats)?;
+            escrow.status = EscrowStatus::Released as u8;
+            escrow.close(payout_wallet.clone())?;
+            // Remaining accounts are not written back by Anchor
+            if let Some(stats) = &buyer_stats {
+                stats.exit(&crate::ID)?;
+            }
+            if let Some(referrer) = &referrer {
+                referrer.exit(&crate::ID)?;
+            }
+        }
+
+        emit_cpi!(BatchEscrowSettled {
+            escrow: group[0].key(),
+            order_id: escrow.order_id,
+            release: true,
+            settled: releasable,
+            merchant_payout,
+            fee,
+            refunded_lamports: 0,
+        });
+    }
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct BatchRefund<'info> {
+    /// Each escrow is refunded only if this signer could refund it on its own
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+}
+
+// remaining_accounts: one REFUND_GROUP per escrow. Escrows that are not refundable
+// right now are skipped and reported, not failed.
+pub fn batch_refund<'info>(ctx: Context<'_, '_, 'info, 'info, BatchRefund<'info>>) -> Result<()> {
+    let groups = ctx.remaining_accounts.chunks_exact(REFUND_GROUP);
+    require!(groups.remainder().is_empty(), EscrowError::InvalidBatch);
+    let now = Clock::get()?.unix_timestamp;
+    let signer = ctx.accounts.authority.key();
+    let paused = ctx.accounts.config.is_paused(PAUSE_REFUND);
+
+    for group in groups {
+        let mut escrow = load_escrow(&group[0])?;
+        let buyer_account = &group[1];
+        require_keys_eq!(buyer_account.key(), escrow.buyer, EscrowError::InvalidBatch);
+        let mut buyer_stats = load_buyer_stats(&group[2], &escrow)?;
+
+        let status = escrow.status;
+        // Same rules as refund_escrow, buyers included while refunds are paused
+        let refundable = (status == EscrowStatus::Funded as u8
+            || status == EscrowStatus::Pending as u8
+            || status == EscrowStatus::Shipped as u8)
+            && (signer == escrow.buyer || !paused)
+            && refund_allowed(&escrow, &ctx.accounts.config, signer, now);
+        let mut refunded_lamports = 0;
+        if refundable {
+            refunded_lamports = escrow.refundable_lamports();
+            pay_from_escrow(&escrow, buyer_account, refunded_lamports)?;
+            settle_exposure(&escrow, &mut buyer_stats)?;
+            escrow.status = EscrowStatus::Refunded as u8;
+            escrow.close(buyer_account.clone())?;
+            if let Some(stats) = &buyer_stats {
+                stats.exit(&crate::ID)?;
+            }
+        }
+
+        emit_cpi!(BatchEscrowSettled {
+            escrow: group[0].key(),
+            order_id: escrow.order_id,
+            release: false,
+            settled: refundable,
+            merchant_payout: 0,
+            fee: 0,
+            refunded_lamports,
+        });
+    }
+    Ok(())
+}
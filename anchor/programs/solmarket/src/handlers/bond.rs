@@ -0,0 +1,399 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+use anchor_lang::Discriminator;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+use super::shared::transfer_tokens;
+use super::sol_escrow::{pay_from_escrow, EscrowError};
+use crate::events::{BondPosted, BondSlashed, BondWithdrawalRequested, BondWithdrawn};
+use crate::state::{Config, Merchant, MerchantBond};
+
+pub const BOND_WITHDRAW_DELAY_DEFAULT: i64 = 14 * 24 * 60 * 60; // 14 days
+
+// Fill in a bond created by init_if_needed on first deposit
+fn init_bond(bond: &mut MerchantBond, merchant: Pubkey, bump: u8) {
+    if bond.merchant == Pubkey::default() {
+        bond.merchant = merchant;
+        bond.bump = bump;
+    }
+}
+
+// Withdrawals run only after a request has waited out Config.bond_withdraw_delay_secs
+fn withdrawal_unlocked(bond: &MerchantBond, config: &Config, now: i64) -> bool {
+    bond.withdraw_requested_at != 0
+        && now >= bond.withdraw_requested_at.saturating_add(config.bond_withdraw_delay_secs)
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct PostBond<'info> {
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    #[account(
+        seeds = [b"merchant", wallet.key().as_ref()],
+        bump = merchant.bump
+    )]
+    pub merchant: Account<'info, Merchant>,
+    #[account(
+        init_if_needed,
+        payer = wallet,
+        space = MerchantBond::DISCRIMINATOR.len() + MerchantBond::INIT_SPACE,
+        seeds = [b"merchant-bond", wallet.key().as_ref()],
+        bump
+    )]
+    pub bond: Account<'info, MerchantBond>,
+}
+
+pub fn post_bond(ctx: Context<PostBond>, lamports: u64) -> Result<()> {
+    require!(lamports > 0, EscrowError::InvalidAmount);
+    let ix = system_program::Transfer {
+        from: ctx.accounts.wallet.to_account_info(),
+        to: ctx.accounts.bond.to_account_info(),
+    };
+    system_program::transfer(CpiContext::new(ctx.accounts.system_program.to_account_info(), ix), lamports)?;
+
+    let bond = &mut ctx.accounts.bond;
+    init_bond(bond, ctx.accounts.wallet.key(), ctx.bumps.bond);
+    bond.lamports = bond.lamports.checked_add(lamports).ok_or(EscrowError::InvalidAmount)?;
+
+    emit_cpi!(BondPosted {
+        merchant: bond.merchant,
+        lamports,
+        tokens: 0,
+        posted_at: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct PostBondTokens<'info> {
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"merchant", wallet.key().as_ref()],
+        bump = merchant.bump
+    )]
+    pub merchant: Account<'info, Merchant>,
+    #[account(address = config.bond_mint @ EscrowError::InvalidBondMint, mint::token_program = token_program)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = wallet,
+        associated_token::token_program = token_program
+    )]
+    pub wallet_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = wallet,
+        space = MerchantBond::DISCRIMINATOR.len() + MerchantBond::INIT_SPACE,
+        seeds = [b"merchant-bond", wallet.key().as_ref()],
+        bump
+    )]
+    pub bond: Account<'info, MerchantBond>,
+    #[account(
+        init_if_needed,
+        payer = wallet,
+        associated_token::mint = mint,
+        associated_token::authority = bond,
+        associated_token::token_program = token_program
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+}
+
+pub fn post_bond_tokens(ctx: Context<PostBondTokens>, amount: u64) -> Result<()> {
+    require!(amount > 0, EscrowError::InvalidAmount);
+    // A bond holds one mint at a time; after a bond_mint change the old tokens have to go first
+    let bond = &ctx.accounts.bond;
+    require!(bond.tokens == 0 || bond.mint == ctx.accounts.mint.key(), EscrowError::InvalidBondMint);
+    transfer_tokens(
+        &ctx.accounts.wallet_token_account,
+        &ctx.accounts.vault,
+        &amount,
+        &ctx.accounts.mint,
+        &ctx.accounts.wallet.to_account_info(),
+        &ctx.accounts.token_program,
+        None,
+    )?;
+
+    let bond = &mut ctx.accounts.bond;
+    init_bond(bond, ctx.accounts.wallet.key(), ctx.bumps.bond);
+    if bond.tokens == 0 {
+        bond.mint = ctx.accounts.mint.key();
+        bond.token_decimals = ctx.accounts.mint.decimals;
+    }
+    bond.tokens = bond.tokens.checked_add(amount).ok_or(EscrowError::InvalidAmount)?;
+
+    emit_cpi!(BondPosted {
+        merchant: bond.merchant,
+        lamports: 0,
+        tokens: amount,
+        posted_at: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RequestBondWithdrawal<'info> {
+    pub wallet: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"merchant-bond", wallet.key().as_ref()],
+        bump = bond.bump
+    )]
+    pub bond: Account<'info, MerchantBond>,
+}
+
+// Start the timelock; a new request replaces any earlier one and restarts the clock
+pub fn request_bond_withdrawal(ctx: Context<RequestBondWithdrawal>, lamports: u64, tokens: u64) -> Result<()> {
+    require!(lamports > 0 || tokens > 0, EscrowError::InvalidAmount);
+    let bond = &mut ctx.accounts.bond;
+    require!(lamports <= bond.lamports && tokens <= bond.tokens, EscrowError::InvalidAmount);
+    let now = Clock::get()?.unix_timestamp;
+    bond.pending_lamports = lamports;
+    bond.pending_tokens = tokens;
+    bond.withdraw_requested_at = now;
+
+    emit_cpi!(BondWithdrawalRequested {
+        merchant: bond.merchant,
+        lamports,
+        tokens,
+        available_at: now.saturating_add(ctx.accounts.config.bond_withdraw_delay_secs),
+    });
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct WithdrawBond<'info> {
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"merchant-bond", wallet.key().as_ref()],
+        bump = bond.bump
+    )]
+    pub bond: Account<'info, MerchantBond>,
+}
+
+pub fn withdraw_bond(ctx: Context<WithdrawBond>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    require!(withdrawal_unlocked(&ctx.accounts.bond, &ctx.accounts.config, now), EscrowError::BondLocked);
+    // Slashing since the request may have left less than was asked for
+    let amount = ctx.accounts.bond.pending_lamports.min(ctx.accounts.bond.lamports);
+    pay_from_escrow(&ctx.accounts.bond, &ctx.accounts.wallet, amount)?;
+
+    let bond = &mut ctx.accounts.bond;
+    bond.lamports -= amount;
+    bond.pending_lamports = 0;
+    if bond.pending_tokens == 0 {
+        bond.withdraw_requested_at = 0;
+    }
+
+    emit_cpi!(BondWithdrawn {
+        merchant: bond.merchant,
+        lamports: amount,
+        tokens: 0,
+    });
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct WithdrawBondTokens<'info> {
+    pub token_program: Interface<'info, TokenInterface>,
+    pub wallet: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+    /// The mint the bond's tokens were posted in, which may predate a bond_mint change
+    #[account(address = bond.mint @ EscrowError::InvalidBondMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = wallet,
+        associated_token::token_program = token_program
+    )]
+    pub wallet_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"merchant-bond", wallet.key().as_ref()],
+        bump = bond.bump
+    )]
+    pub bond: Account<'info, MerchantBond>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = bond,
+        associated_token::token_program = token_program
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+}
+
+pub fn withdraw_bond_tokens(ctx: Context<WithdrawBondTokens>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    require!(withdrawal_unlocked(&ctx.accounts.bond, &ctx.accounts.config, now), EscrowError::BondLocked);
+    let amount = ctx.accounts.bond.pending_tokens.min(ctx.accounts.bond.tokens);
+    let seeds: [&[u8]; 3] = [b"merchant-bond", ctx.accounts.bond.merchant.as_ref(), &[ctx.accounts.bond.bump]];
+    transfer_tokens(
+        &ctx.accounts.vault,
+        &ctx.accounts.wallet_token_account,
+        &amount,
+        &ctx.accounts.mint,
+        &ctx.accounts.bond.to_account_info(),
+        &ctx.accounts.token_program,
+        Some(&seeds[..]),
+    )?;
+
+    let bond = &mut ctx.accounts.bond;
+    bond.tokens -= amount;
+    bond.pending_tokens = 0;
+    if bond.pending_lamports == 0 {
+        bond.withdraw_requested_at = 0;
+    }
+
+    emit_cpi!(BondWithdrawn {
+        merchant: bond.merchant,
+        lamports: 0,
+        tokens: amount,
+    });
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SlashBond<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = authority @ EscrowError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    /// CHECK: compensated party, usually the buyer of the failed order
+    #[account(mut)]
+    pub recipient: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"merchant-bond", bond.merchant.as_ref()],
+        bump = bond.bump
+    )]
+    pub bond: Account<'info, MerchantBond>,
+}
+
+// Platform authority pays a buyer out of the merchant's bond, e.g. after a lost dispute
+pub fn slash_bond(ctx: Context<SlashBond>, lamports: u64) -> Result<()> {
+    require!(lamports > 0 && lamports <= ctx.accounts.bond.lamports, EscrowError::InvalidAmount);
+    pay_from_escrow(&ctx.accounts.bond, &ctx.accounts.recipient, lamports)?;
+
+    let bond = &mut ctx.accounts.bond;
+    bond.lamports -= lamports;
+    bond.pending_lamports = bond.pending_lamports.min(bond.lamports);
+    bond.total_slashed_lamports = bond.total_slashed_lamports.saturating_add(lamports);
+
+    emit_cpi!(BondSlashed {
+        merchant: bond.merchant,
+        recipient: ctx.accounts.recipient.key(),
+        lamports,
+        tokens: 0,
+        slashed_at: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SlashBondTokens<'info> {
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = authority @ EscrowError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    /// The mint the bond's tokens were posted in, which may predate a bond_mint change
+    #[account(address = bond.mint @ EscrowError::InvalidBondMint)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: compensated party, usually the buyer of the failed order
+    pub recipient: UncheckedAccount<'info>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = mint,
+        associated_token::authority = recipient,
+        associated_token::token_program = token_program
+    )]
+    pub recipient_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"merchant-bond", bond.merchant.as_ref()],
+        bump = bond.bump
+    )]
+    pub bond: Account<'info, MerchantBond>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = bond,
+        associated_token::token_program = token_program
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+}
+
+pub fn slash_bond_tokens(ctx: Context<SlashBondTokens>, amount: u64) -> Result<()> {
+    require!(amount > 0 && amount <= ctx.accounts.bond.tokens, EscrowError::InvalidAmount);
+    let seeds: [&[u8]; 3] = [b"merchant-bond", ctx.accounts.bond.merchant.as_ref(), &[ctx.accounts.bond.bump]];
+    transfer_tokens(
+        &ctx.accounts.vault,
+        &ctx.accounts.recipient_token_account,
+        &amount,
+        &ctx.accounts.mint,
+        &ctx.accounts.bond.to_account_info(),
+        &ctx.accounts.token_program,
+        Some(&seeds[..]),
+    )?;
+
+    let bond = &mut ctx.accounts.bond;
+    bond.tokens -= amount;
+    bond.pending_tokens = bond.pending_tokens.min(bond.tokens);
+    bond.total_slashed_tokens = bond.total_slashed_tokens.saturating_add(amount);
+
+    emit_cpi!(BondSlashed {
+        merchant: bond.merchant,
+        recipient: ctx.accounts.recipient.key(),
+        lamports: 0,
+        tokens: amount,
+        slashed_at: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
@@ -0,0 +1,31 @@
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use crate::state::BuyerStats;
+
+#[derive(Accounts)]
+pub struct InitializeBuyerStats<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: buyer the stats belong to
+    pub buyer: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = BuyerStats::DISCRIMINATOR.len() + BuyerStats::INIT_SPACE,
+        seeds = [b"buyer-stats", buyer.key().as_ref()],
+        bump
+    )]
+    pub buyer_stats: Account<'info, BuyerStats>,
+}
+
+// Anyone may open a buyer's stats; they are required to fund an escrow once buyer caps are set
+pub fn initialize_buyer_stats(ctx: Context<InitializeBuyerStats>) -> Result<()> {
+    ctx.accounts.buyer_stats.set_inner(BuyerStats {
+        buyer: ctx.accounts.buyer.key(),
+        open_escrows: 0,
+        lamports_in_flight: 0,
+        bump: ctx.bumps.buyer_stats,
+    });
+    Ok(())
+}
@@ -0,0 +1,282 @@
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use anchor_lang::system_program;
+use crate::events::{CartCreated, CartFunded, CartItemSettled};
+use crate::state::{
+    CartEscrow, CartItem, CartLine, Config, EscrowStatus, Merchant, Treasury, MAX_CART_ITEMS, PAUSE_CREATE,
+    PAUSE_FUND, PAUSE_REFUND, PAUSE_RELEASE,
+};
+use super::shared::compute_fee;
+use super::sol_escrow::{pay_fee, pay_from_escrow, EscrowError};
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(cart_id: u64)]
+pub struct InitializeCart<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+    /// CHECK: Buyer paying for the cart; often equals payer
+    pub buyer: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = CartEscrow::DISCRIMINATOR.len() + CartEscrow::INIT_SPACE,
+        seeds = [b"cart-escrow", buyer.key().as_ref(), &cart_id.to_le_bytes()],
+        bump
+    )]
+    pub cart: Account<'info, CartEscrow>,
+}
+
+// remaining_accounts: the registered Merchant account for each line, in line order
+pub fn initialize_cart<'info>(
+    ctx: Context<'_, '_, 'info, 'info, InitializeCart<'info>>,
+    cart_id: u64,
+    expires_at: i64,
+    lines: Vec<CartLine>,
+) -> Result<()> {
+    require!(!ctx.accounts.config.is_paused(PAUSE_CREATE), EscrowError::Paused);
+    require!(!lines.is_empty() && lines.len() <= MAX_CART_ITEMS, EscrowError::InvalidCart);
+    require!(lines.iter().all(|l| l.amount_lamports > 0), EscrowError::InvalidAmount);
+    let total = lines
+        .iter()
+        .try_fold(0u64, |acc, l| acc.checked_add(l.amount_lamports))
+        .ok_or(EscrowError::InvalidAmount)?;
+    let now = Clock::get()?.unix_timestamp;
+    require!(expires_at > now, EscrowError::InvalidExpiry);
+    require!(ctx.remaining_accounts.len() == lines.len(), EscrowError::MerchantNotRegistered);
+
+    let mut items = Vec::with_capacity(lines.len());
+    for (line, info) in lines.iter().zip(ctx.remaining_accounts.iter()) {
+        let merchant = Account::<Merchant>::try_from(info)?;
+        let expected = Pubkey::create_program_address(&[b"merchant", line.merchant.as_ref(), &[merchant.bump]], &crate::ID)
+            .map_err(|_| EscrowError::MerchantNotRegistered)?;
+        require_keys_eq!(info.key(), expected, EscrowError::MerchantNotRegistered);
+        require!(!merchant.suspended, EscrowError::MerchantSuspended);
+        require!(merchant.verified || !ctx.accounts.config.require_verified_merchants, EscrowError::MerchantNotVerified);
+        items.push(CartItem {
+            merchant: line.merchant,
+            amount_lamports: line.amount_lamports,
+            fee_bps: merchant.fee_bps(ctx.accounts.config.fee_bps),
+            status: EscrowStatus::Pending as u8,
+        });
+    }
+
+    ctx.accounts.cart.set_inner(CartEscrow {
+        cart_id,
+        buyer: ctx.accounts.buyer.key(),
+        payer: ctx.accounts.payer.key(),
+        status: EscrowStatus::Pending as u8,
+        bump: ctx.bumps.cart,
+        created_at: now,
+        expires_at,
+        items,
+    });
+
+    emit_cpi!(CartCreated {
+        cart_id,
+        buyer: ctx.accounts.buyer.key(),
+        total_lamports: total,
+        item_count: lines.len() as u8,
+        created_at: now,
+        expires_at,
+    });
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct FundCart<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        has_one = buyer @ EscrowError::Unauthorized,
+        seeds = [b"cart-escrow", cart.buyer.as_ref(), &cart.cart_id.to_le_bytes()],
+        bump = cart.bump
+    )]
+    pub cart: Account<'info, CartEscrow>,
+}
+
+// Buyer funds every line of the cart in a single transfer
+pub fn fund_cart(ctx: Context<FundCart>) -> Result<()> {
+    require!(!ctx.accounts.config.is_paused(PAUSE_FUND), EscrowError::Paused);
+    require!(ctx.accounts.cart.status == EscrowStatus::Pending as u8, EscrowError::InvalidState);
+    // Lines refunded before funding stay refunded and are not charged
+    let pending = EscrowStatus::Pending as u8;
+    let total: u64 = ctx.accounts.cart.items.iter().filter(|i| i.status == pending).map(|i| i.amount_lamports).sum();
+    let ix = system_program::Transfer {
+        from: ctx.accounts.buyer.to_account_info(),
+        to: ctx.accounts.cart.to_account_info(),
+    };
+    let cpi = CpiContext::new(ctx.accounts.system_program.to_account_info(), ix);
+    system_program::transfer(cpi, total)?;
+
+    let cart = &mut ctx.accounts.cart;
+    cart.status = EscrowStatus::Funded as u8;
+    for item in cart.items.iter_mut().filter(|i| i.status == pending) {
+        item.status = EscrowStatus::Funded as u8;
+    }
+
+    emit_cpi!(CartFunded {
+        cart_id: cart.cart_id,
+        buyer: cart.buyer,
+        total_lamports: total,
+        funded_at: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ReleaseCartItem<'info> {
+    /// Line merchant or authority may release
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"merchant", merchant_account.wallet.as_ref()],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, Merchant>,
+    /// CHECK: merchant's registered payout wallet
+    #[account(mut, address = merchant_account.payout_wallet)]
+    pub payout_wallet: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump = treasury.bump
+    )]
+    pub treasury: Account<'info, Treasury>,
+    /// CHECK: rent payer, receives the cart's rent once every line settles
+    #[account(mut, address = cart.payer)]
+    pub payer: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"cart-escrow", cart.buyer.as_ref(), &cart.cart_id.to_le_bytes()],
+        bump = cart.bump
+    )]
+    pub cart: Account<'info, CartEscrow>,
+}
+
+pub fn release_cart_item(ctx: Context<ReleaseCartItem>, index: u8) -> Result<()> {
+    require!(!ctx.accounts.config.is_paused(PAUSE_RELEASE), EscrowError::Paused);
+    let item = *ctx.accounts.cart.items.get(index as usize).ok_or(EscrowError::InvalidCart)?;
+    require!(item.status == EscrowStatus::Funded as u8, EscrowError::InvalidState);
+    require_keys_eq!(ctx.accounts.merchant_account.wallet, item.merchant, EscrowError::InvalidCart);
+    let now = Clock::get()?.unix_timestamp;
+    let signer = ctx.accounts.authority.key();
+    let grace_end = ctx.accounts.cart.expires_at.saturating_add(ctx.accounts.config.expiry_grace_secs);
+    require!(
+        signer == ctx.accounts.config.authority || (signer == item.merchant && now < grace_end),
+        EscrowError::Unauthorized
+    );
+
+    let fee = compute_fee(item.amount_lamports, item.fee_bps);
+    let pay_merchant = item.amount_lamports - fee;
+    pay_from_escrow(&ctx.accounts.cart, &ctx.accounts.payout_wallet, pay_merchant)?;
+    pay_fee(&ctx.accounts.cart, 0, &ctx.accounts.treasury, &mut None, fee)?;
+    ctx.accounts.treasury.record_release(item.amount_lamports, fee, true);
+
+    let cart = &mut ctx.accounts.cart;
+    cart.items[index as usize].status = EscrowStatus::Released as u8;
+    emit_cpi!(CartItemSettled {
+        cart_id: cart.cart_id,
+        buyer: cart.buyer,
+        merchant: item.merchant,
+        index,
+        status: EscrowStatus::Released as u8,
+        merchant_payout: pay_merchant,
+        fee,
+        refunded_lamports: 0,
+        settled_at: now,
+    });
+    close_if_settled(cart, &ctx.accounts.payer)
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RefundCartItem<'info> {
+    /// Buyer or authority may refund; anyone once the grace window has closed
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+    /// CHECK: refund recipient (buyer)
+    #[account(mut, address = cart.buyer)]
+    pub buyer_account: UncheckedAccount<'info>,
+    /// CHECK: rent payer, receives the cart's rent once every line settles
+    #[account(mut, address = cart.payer)]
+    pub payer: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"cart-escrow", cart.buyer.as_ref(), &cart.cart_id.to_le_bytes()],
+        bump = cart.bump
+    )]
+    pub cart: Account<'info, CartEscrow>,
+}
+
+// Refunding a line of an unfunded cart just settles it so the cart can close
+pub fn refund_cart_item(ctx: Context<RefundCartItem>, index: u8) -> Result<()> {
+    // PAUSE_REFUND stops authority and post-grace refunds, not the buyer settling their own lines
+    require!(
+        ctx.accounts.authority.key() == ctx.accounts.cart.buyer || !ctx.accounts.config.is_paused(PAUSE_REFUND),
+        EscrowError::Paused
+    );
+    let item = *ctx.accounts.cart.items.get(index as usize).ok_or(EscrowError::InvalidCart)?;
+    let funded = item.status == EscrowStatus::Funded as u8;
+    require!(funded || item.status == EscrowStatus::Pending as u8, EscrowError::InvalidState);
+    let now = Clock::get()?.unix_timestamp;
+    let signer = ctx.accounts.authority.key();
+    let grace_end = ctx.accounts.cart.expires_at.saturating_add(ctx.accounts.config.expiry_grace_secs);
+    require!(
+        signer == ctx.accounts.config.authority
+            || (signer == ctx.accounts.cart.buyer && now < ctx.accounts.cart.expires_at)
+            || now >= grace_end,
+        EscrowError::Unauthorized
+    );
+
+    let refunded = if funded { item.amount_lamports } else { 0 };
+    pay_from_escrow(&ctx.accounts.cart, &ctx.accounts.buyer_account, refunded)?;
+
+    let cart = &mut ctx.accounts.cart;
+    cart.items[index as usize].status = EscrowStatus::Refunded as u8;
+    emit_cpi!(CartItemSettled {
+        cart_id: cart.cart_id,
+        buyer: cart.buyer,
+        merchant: item.merchant,
+        index,
+        status: EscrowStatus::Refunded as u8,
+        merchant_payout: 0,
+        fee: 0,
+        refunded_lamports: refunded,
+        settled_at: now,
+    });
+    close_if_settled(cart, &ctx.accounts.payer)
+}
+
+fn close_if_settled<'info>(cart: &mut Account<'info, CartEscrow>, payer: &UncheckedAccount<'info>) -> Result<()> {
+    let settled = cart.items.iter().all(|i| {
+        i.status == EscrowStatus::Released as u8 || i.status == EscrowStatus::Refunded as u8
+    });
+    if settled {
+        cart.close(payer.to_account_info())?;
+    }
+    Ok(())
+}
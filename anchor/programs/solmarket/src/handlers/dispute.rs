@@ -0,0 +1,183 @@
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use crate::events::{EscrowDisputeResolved, EscrowDisputed, ReferralPaid};
+use crate::state::{BuyerStats, Config, Dispute, EscrowStatus, Merchant, Referrer, SolEscrow, Treasury, PAUSE_RELEASE};
+use super::shared::compute_fee;
+use super::sol_escrow::{pay_fee, pay_from_escrow, settle_exposure, EscrowError};
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RaiseDispute<'info> {
+    /// Buyer or merchant may raise
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"sol-escrow", escrow.buyer.as_ref(), &escrow.order_id.to_le_bytes()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, SolEscrow>,
+    #[account(
+        init,
+        payer = authority,
+        space = Dispute::DISCRIMINATOR.len() + Dispute::INIT_SPACE,
+        seeds = [b"dispute", escrow.key().as_ref(), &escrow.created_at.to_le_bytes()],
+        bump
+    )]
+    pub dispute: Account<'info, Dispute>,
+}
+
+pub fn raise_dispute(ctx: Context<RaiseDispute>) -> Result<()> {
+    let escrow = &ctx.accounts.escrow;
+    let now = Clock::get()?.unix_timestamp;
+    if escrow.status == EscrowStatus::Shipped as u8 {
+        // Shipped orders stay disputable until the buyer's review period ends
+        let review_end = escrow.shipped_at.saturating_add(ctx.accounts.config.review_period_secs);
+        require!(now < escrow.expires_at.max(review_end), EscrowError::Expired);
+    } else {
+        require!(escrow.status == EscrowStatus::Funded as u8, EscrowError::InvalidState);
+        require!(now < escrow.expires_at, EscrowError::Expired);
+    }
+    let signer = ctx.accounts.authority.key();
+    require!(signer == ctx.accounts.escrow.buyer || signer == ctx.accounts.escrow.merchant, EscrowError::Unauthorized);
+
+    ctx.accounts.dispute.set_inner(Dispute {
+        escrow: ctx.accounts.escrow.key(),
+        order_id: ctx.accounts.escrow.order_id,
+        raised_by: signer,
+        raised_at: now,
+        arbiter: Pubkey::default(),
+        buyer_bps: 0,
+        resolved_at: 0,
+        bump: ctx.bumps.dispute,
+    });
+    // Disputed escrows are frozen: release, refund and expiry all require another status
+    let escrow = &mut ctx.accounts.escrow;
+    escrow.status = EscrowStatus::Disputed as u8;
+
+    emit_cpi!(EscrowDisputed {
+        order_id: escrow.order_id,
+        buyer: escrow.buyer,
+        merchant: escrow.merchant,
+        raised_by: signer,
+        disputed_lamports: escrow.remaining_lamports(),
+        raised_at: now,
+    });
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ResolveDispute<'info> {
+    pub arbiter: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = arbiter @ EscrowError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    /// CHECK: buyer's share recipient
+    #[account(mut, address = escrow.buyer)]
+    pub buyer_account: UncheckedAccount<'info>,
+    #[account(
+        seeds = [b"merchant", escrow.merchant.as_ref()],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, Merchant>,
+    /// CHECK: merchant's registered payout wallet
+    #[account(mut, address = merchant_account.payout_wallet)]
+    pub payout_wallet: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump = treasury.bump
+    )]
+    pub treasury: Account<'info, Treasury>,
+    /// The escrow's referrer; required when it has one and the merchant's share carries a fee
+    #[account(
+        mut,
+        seeds = [b"referrer", escrow.referrer.as_ref()],
+        bump = referrer.bump
+    )]
+    pub referrer: Option<Account<'info, Referrer>>,
+    /// Buyer's exposure stats; required when the escrow is tracked in them
+    #[account(
+        mut,
+        seeds = [b"buyer-stats", escrow.buyer.as_ref()],
+        bump = buyer_stats.bump
+    )]
+    pub buyer_stats: Option<Account<'info, BuyerStats>>,
+    #[account(
+        mut,
+        close = buyer_account,
+        seeds = [b"sol-escrow", escrow.buyer.as_ref(), &escrow.order_id.to_le_bytes()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, SolEscrow>,
+    #[account(
+        mut,
+        seeds = [b"dispute", escrow.key().as_ref(), &escrow.created_at.to_le_bytes()],
+        bump = dispute.bump
+    )]
+    pub dispute: Account<'info, Dispute>,
+}
+
+// Split the escrowed amount between buyer and merchant; the fee only applies to the merchant's share
+pub fn resolve_dispute(ctx: Context<ResolveDispute>, buyer_bps: u16) -> Result<()> {
+    // Awarding everything to the buyer is a refund, which withdraw-only mode still allows
+    require!(
+        buyer_bps == 10_000 || !ctx.accounts.config.is_paused(PAUSE_RELEASE),
+        EscrowError::Paused
+    );
+    require!(ctx.accounts.escrow.status == EscrowStatus::Disputed as u8, EscrowError::InvalidState);
+    require!(buyer_bps <= 10_000, EscrowError::InvalidBps);
+
+    let total = ctx.accounts.escrow.remaining_lamports();
+    let to_buyer = (total as u128 * buyer_bps as u128 / 10_000) as u64;
+    let merchant_share = total - to_buyer;
+    let fee = compute_fee(merchant_share, ctx.accounts.escrow.fee_bps);
+
+    pay_from_escrow(&ctx.accounts.escrow, &ctx.accounts.buyer_account, to_buyer)?;
+    pay_from_escrow(&ctx.accounts.escrow, &ctx.accounts.payout_wallet, merchant_share - fee)?;
+    let referral_bps = ctx.accounts.escrow.referral_bps;
+    let (platform_fee, referral_fee) =
+        pay_fee(&ctx.accounts.escrow, referral_bps, &ctx.accounts.treasury, &mut ctx.accounts.referrer, fee)?;
+    ctx.accounts.treasury.record_release(merchant_share, platform_fee, true);
+    settle_exposure(&ctx.accounts.escrow, &mut ctx.accounts.buyer_stats)?;
+
+    let now = Clock::get()?.unix_timestamp;
+    let dispute = &mut ctx.accounts.dispute;
+    dispute.arbiter = ctx.accounts.arbiter.key();
+    dispute.buyer_bps = buyer_bps;
+    dispute.resolved_at = now;
+    let escrow = &mut ctx.accounts.escrow;
+    escrow.status = EscrowStatus::Resolved as u8;
+    if referral_fee > 0 {
+        emit_cpi!(ReferralPaid {
+            order_id: escrow.order_id,
+            referrer: escrow.referrer,
+            fee,
+            platform_fee,
+            referral_fee,
+        });
+    }
+
+    emit_cpi!(EscrowDisputeResolved {
+        order_id: escrow.order_id,
+        buyer: escrow.buyer,
+        merchant: escrow.merchant,
+        arbiter: dispute.arbiter,
+        buyer_bps,
+        buyer_refund: to_buyer,
+        merchant_payout: merchant_share - fee,
+        fee,
+        resolved_at: now,
+    });
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use crate::state::{Config, Merchant};
+use super::admin::MAX_FEE_BPS;
+use super::sol_escrow::EscrowError;
+
+#[derive(Accounts)]
+pub struct RegisterMerchant<'info> {
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: wallet that receives releases; may equal the signing wallet
+    pub payout_wallet: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = wallet,
+        space = Merchant::DISCRIMINATOR.len() + Merchant::INIT_SPACE,
+        seeds = [b"merchant", wallet.key().as_ref()],
+        bump
+    )]
+    pub merchant: Account<'info, Merchant>,
+}
+
+pub fn register_merchant(ctx: Context<RegisterMerchant>) -> Result<()> {
+    ctx.accounts.merchant.set_inner(Merchant {
+        wallet: ctx.accounts.wallet.key(),
+        payout_wallet: ctx.accounts.payout_wallet.key(),
+        verified: false,
+        suspended: false,
+        fee_bps_override: None,
+        registered_at: Clock::get()?.unix_timestamp,
+        bump: ctx.bumps.merchant,
+    });
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetPayoutWallet<'info> {
+    pub wallet: Signer<'info>,
+    /// CHECK: new payout wallet
+    pub payout_wallet: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        has_one = wallet @ EscrowError::Unauthorized,
+        seeds = [b"merchant", merchant.wallet.as_ref()],
+        bump = merchant.bump
+    )]
+    pub merchant: Account<'info, Merchant>,
+}
+
+pub fn set_payout_wallet(ctx: Context<SetPayoutWallet>) -> Result<()> {
+    ctx.accounts.merchant.payout_wallet = ctx.accounts.payout_wallet.key();
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct UpdateMerchant<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = authority @ EscrowError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"merchant", merchant.wallet.as_ref()],
+        bump = merchant.bump
+    )]
+    pub merchant: Account<'info, Merchant>,
+}
+
+pub fn set_merchant_verified(ctx: Context<UpdateMerchant>, verified: bool) -> Result<()> {
+    ctx.accounts.merchant.verified = verified;
+    Ok(())
+}
+
+pub fn set_merchant_suspended(ctx: Context<UpdateMerchant>, suspended: bool) -> Result<()> {
+    ctx.accounts.merchant.suspended = suspended;
+    Ok(())
+}
+
+pub fn set_merchant_fee(ctx: Context<UpdateMerchant>, fee_bps_override: Option<u16>) -> Result<()> {
+    if let Some(fee_bps) = fee_bps_override {
+        require!(fee_bps <= MAX_FEE_BPS, EscrowError::FeeTooHigh);
+    }
+    ctx.accounts.merchant.fee_bps_override = fee_bps_override;
+    Ok(())
+}
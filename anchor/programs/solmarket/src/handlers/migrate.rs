@@ -0,0 +1,122 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+use anchor_lang::Discriminator;
+use crate::state::{Config, SolEscrow, CONFIG_VERSION, SOL_ESCROW_VERSION};
+use super::bond::BOND_WITHDRAW_DELAY_DEFAULT;
+use super::sol_escrow::{EscrowError, CRANK_BOUNTY_DEFAULT, MAX_ESCROW_DURATION_DEFAULT, REVIEW_PERIOD_DEFAULT};
+
+// Older layouts are strict prefixes of the current one, so upgrading is:
+// 1. realloc to the current size, zero-filling the new fields (payer tops up rent)
+// 2. deserialize with the current struct and fix up fields whose zero value is wrong
+// 3. stamp the current version
+fn grow_account<'info>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    new_len: usize,
+) -> Result<()> {
+    require!(account.owner == &crate::ID, EscrowError::InvalidState);
+    if account.data_len() >= new_len {
+        return Ok(());
+    }
+    // Rent for the added bytes only; an escrow's balance also holds the buyer's funds
+    let rent = Rent::get()?;
+    let top_up = rent.minimum_balance(new_len).saturating_sub(rent.minimum_balance(account.data_len()));
+    if top_up > 0 {
+        let ix = system_program::Transfer {
+            from: payer.clone(),
+            to: account.clone(),
+        };
+        system_program::transfer(CpiContext::new(system_program.clone(), ix), top_up)?;
+    }
+    account.realloc(new_len, true)?;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct MigrateConfig<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: may still hold an older layout; owner and discriminator are checked in the handler
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: UncheckedAccount<'info>,
+}
+
+pub fn migrate_config(ctx: Context<MigrateConfig>) -> Result<()> {
+    let info = ctx.accounts.config.to_account_info();
+    grow_account(
+        &info,
+        &ctx.accounts.authority.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        Config::DISCRIMINATOR.len() + Config::INIT_SPACE,
+    )?;
+
+    let mut config = Config::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+    require_keys_eq!(config.authority, ctx.accounts.authority.key(), EscrowError::Unauthorized);
+    if config.version >= CONFIG_VERSION {
+        return Ok(());
+    }
+    if config.version < 1 {
+        config.pending_authority = Pubkey::default();
+        config.arbiter = config.authority;
+        config.review_period_secs = REVIEW_PERIOD_DEFAULT;
+    }
+    if config.version < 2 {
+        config.max_escrow_duration_secs = MAX_ESCROW_DURATION_DEFAULT;
+    }
+    if config.version < 6 {
+        config.bond_withdraw_delay_secs = BOND_WITHDRAW_DELAY_DEFAULT;
+    }
+    if config.version < 8 {
+        config.crank_bounty_lamports = CRANK_BOUNTY_DEFAULT;
+    }
+    config.version = CONFIG_VERSION;
+    config.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct MigrateEscrow<'info> {
+    /// Anyone may migrate; they fund the extra rent
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: may still hold an older layout; owner, discriminator and seeds are checked in the handler
+    #[account(mut)]
+    pub escrow: UncheckedAccount<'info>,
+}
+
+// Lazy per-escrow migration: clients prepend this to any instruction touching an
+// escrow created under an older layout. It is a no-op for current escrows.
+pub fn migrate_escrow(ctx: Context<MigrateEscrow>) -> Result<()> {
+    let info = ctx.accounts.escrow.to_account_info();
+    grow_account(
+        &info,
+        &ctx.accounts.payer.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        SolEscrow::DISCRIMINATOR.len() + SolEscrow::INIT_SPACE,
+    )?;
+
+    let mut escrow = SolEscrow::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+    let expected = Pubkey::create_program_address(
+        &[b"sol-escrow", escrow.buyer.as_ref(), &escrow.order_id.to_le_bytes(), &[escrow.bump]],
+        &crate::ID,
+    )
+    .map_err(|_| EscrowError::InvalidState)?;
+    require_keys_eq!(info.key(), expected, EscrowError::InvalidState);
+    if escrow.version >= SOL_ESCROW_VERSION {
+        return Ok(());
+    }
+    if escrow.version < 1 {
+        // The original layout did not record who paid the rent; the buyer usually did
+        escrow.payer = escrow.buyer;
+    }
+    escrow.version = SOL_ESCROW_VERSION;
+    escrow.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+    Ok(())
+}
@@ -1,4 +1,30 @@
 pub mod shared;
 pub use shared::*;
 pub mod sol_escrow;
-pub use sol_escrow::*;
\ No newline at end of file
+pub use sol_escrow::*;
+pub mod order_intent;
+pub use order_intent::*;
+pub mod token_escrow;
+pub use token_escrow::*;
+pub mod cart_escrow;
+pub use cart_escrow::*;
+pub mod subscription;
+pub use subscription::*;
+pub mod treasury;
+pub use treasury::*;
+pub mod referrer;
+pub use referrer::*;
+pub mod merchant;
+pub use merchant::*;
+pub mod buyer_stats;
+pub use buyer_stats::*;
+pub mod bond;
+pub use bond::*;
+pub mod migrate;
+pub use migrate::*;
+pub mod admin;
+pub use admin::*;
+pub mod dispute;
+pub use dispute::*;
+pub mod batch;
+pub use batch::*;
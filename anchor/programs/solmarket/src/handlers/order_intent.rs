@@ -0,0 +1,24 @@
+use anchor_lang::prelude::*;
+use crate::state::OrderIntent;
+use super::sol_escrow::EscrowError;
+
+#[derive(Accounts)]
+pub struct CloseOrderIntent<'info> {
+    /// CHECK: rent payer of the intent record
+    #[account(mut, address = intent.payer)]
+    pub payer: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        close = payer,
+        seeds = [b"order-intent", intent.buyer.as_ref(), &intent.nonce.to_le_bytes()],
+        bump = intent.bump
+    )]
+    pub intent: Account<'info, OrderIntent>,
+}
+
+// Permissionless once the intent has expired; its rent goes back to the payer
+pub fn close_order_intent(ctx: Context<CloseOrderIntent>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    require!(now >= ctx.accounts.intent.expires_at, EscrowError::NotExpired);
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use crate::state::{Config, Referrer};
+use super::sol_escrow::{pay_from_escrow, EscrowError};
+
+#[derive(Accounts)]
+pub struct RegisterReferrer<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = authority @ EscrowError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    /// CHECK: affiliate wallet that claims the accrued fees; need not exist yet
+    pub wallet: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = authority,
+        space = Referrer::DISCRIMINATOR.len() + Referrer::INIT_SPACE,
+        seeds = [b"referrer", wallet.key().as_ref()],
+        bump
+    )]
+    pub referrer: Account<'info, Referrer>,
+}
+
+// Only the platform registers referrers, so payers cannot invent one to rebate themselves
+pub fn register_referrer(ctx: Context<RegisterReferrer>, referral_bps: u16) -> Result<()> {
+    require!(referral_bps <= ctx.accounts.config.max_referral_bps, EscrowError::ReferralTooHigh);
+    ctx.accounts.referrer.set_inner(Referrer {
+        wallet: ctx.accounts.wallet.key(),
+        referral_bps,
+        total_earned: 0,
+        total_claimed: 0,
+        registered_at: Clock::get()?.unix_timestamp,
+        bump: ctx.bumps.referrer,
+    });
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct UpdateReferrer<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = authority @ EscrowError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"referrer", referrer.wallet.as_ref()],
+        bump = referrer.bump
+    )]
+    pub referrer: Account<'info, Referrer>,
+}
+
+// Applies to escrows created afterwards; open ones keep the share they were created with
+pub fn set_referral_bps(ctx: Context<UpdateReferrer>, referral_bps: u16) -> Result<()> {
+    require!(referral_bps <= ctx.accounts.config.max_referral_bps, EscrowError::ReferralTooHigh);
+    ctx.accounts.referrer.referral_bps = referral_bps;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct ClaimReferralFees<'info> {
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+    #[account(
+        mut,
+        has_one = wallet @ EscrowError::Unauthorized,
+        seeds = [b"referrer", wallet.key().as_ref()],
+        bump = referrer.bump
+    )]
+    pub referrer: Account<'info, Referrer>,
+}
+
+// Pay out everything accrued; the account keeps its rent-exempt minimum
+pub fn claim_referral_fees(ctx: Context<ClaimReferralFees>) -> Result<()> {
+    let info = ctx.accounts.referrer.to_account_info();
+    let rent_floor = Rent::get()?.minimum_balance(info.data_len());
+    let amount = info.lamports().saturating_sub(rent_floor);
+    require!(amount > 0, EscrowError::InvalidAmount);
+    pay_from_escrow(&ctx.accounts.referrer, &ctx.accounts.wallet, amount)?;
+    let referrer = &mut ctx.accounts.referrer;
+    referrer.total_claimed = referrer.total_claimed.saturating_add(amount);
+    Ok(())
+}
@@ -1,9 +1,15 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{ed25519_program, sysvar::instructions::get_instruction_relative};
 
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{transfer_fee::TransferFeeConfig, BaseStateWithExtensions, StateWithExtensions},
+};
 use anchor_spl::token_interface::{
     close_account, transfer_checked, CloseAccount, Mint, TokenAccount, TokenInterface,
     TransferChecked,
 };
+use super::sol_escrow::EscrowError;
 
 // Transfer tokens from one account to another
 // If transferring from a token account owned by a PDA, owning_pda_seeds must be provided.
@@ -66,4 +72,52 @@ pub fn close_token_account<'info>(
     } else {
         CpiContext::new(token_program.to_account_info(), close_accounts)
     })
-}
\ No newline at end of file
+}
+
+// Token-2022 mints with a transfer fee deliver less than was sent, which would leave
+// vaults short of the escrowed amount
+pub fn has_transfer_fee(mint: &InterfaceAccount<Mint>) -> Result<bool> {
+    let info = mint.to_account_info();
+    if *info.owner != spl_token_2022::ID {
+        return Ok(false);
+    }
+    let data = info.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    Ok(state.get_extension::<TransferFeeConfig>().is_ok())
+}
+
+// Platform fee on an amount, rounded down
+pub fn compute_fee(amount: u64, fee_bps: u16) -> u64 {
+    (amount as u128 * fee_bps as u128 / 10_000) as u64
+}
+
+// Check that the instruction right before this one is an Ed25519 program instruction
+// verifying `signer`'s signature over exactly `message`. The Ed25519 program has
+// already checked the signature itself, we only check what it was checking.
+pub fn verify_ed25519_signature(instructions: &AccountInfo, signer: &Pubkey, message: &[u8]) -> Result<()> {
+    let ix = get_instruction_relative(-1, instructions).map_err(|_| EscrowError::InvalidSignature)?;
+    require_keys_eq!(ix.program_id, ed25519_program::ID, EscrowError::InvalidSignature);
+    require!(ix.accounts.is_empty(), EscrowError::InvalidSignature);
+
+    // [count: u8, padding: u8, then one 14 byte offsets record per signature]
+    let data = &ix.data;
+    require!(data.len() >= 16 && data[0] == 1, EscrowError::InvalidSignature);
+    let read_u16 = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    let signature_ix = read_u16(4);
+    let public_key_offset = read_u16(6) as usize;
+    let public_key_ix = read_u16(8);
+    let message_offset = read_u16(10) as usize;
+    let message_size = read_u16(12) as usize;
+    let message_ix = read_u16(14);
+    // u16::MAX means the data lives in the Ed25519 instruction itself
+    require!(
+        signature_ix == u16::MAX && public_key_ix == u16::MAX && message_ix == u16::MAX,
+        EscrowError::InvalidSignature
+    );
+
+    let public_key = data.get(public_key_offset..public_key_offset + 32).ok_or(EscrowError::InvalidSignature)?;
+    let signed = data.get(message_offset..message_offset + message_size).ok_or(EscrowError::InvalidSignature)?;
+    require!(public_key == signer.as_ref(), EscrowError::InvalidSignature);
+    require!(signed == message, EscrowError::InvalidSignature);
+    Ok(())
+}
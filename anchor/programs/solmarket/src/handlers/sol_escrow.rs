@@ -1,9 +1,142 @@
 use anchor_lang::prelude::*;
 use anchor_lang::Discriminator;
+use anchor_lang::solana_program::{hash, sysvar};
 use anchor_lang::system_program;
-use crate::state::{Config, EscrowStatus, SolEscrow};
+use crate::state::{
+    BuyerStats, Config, Dispute, EscrowStatus, FeeSplit, Merchant, MerchantBond, OrderIntent, Referrer, SolEscrow, Treasury, CONFIG_VERSION, MAX_FEE_SPLITS,
+    MAX_MILESTONES, PAUSE_CREATE, PAUSE_FUND, PAUSE_REFUND, PAUSE_RELEASE, SOL_ESCROW_VERSION,
+};
+use crate::events::{
+    CrankRewarded, EscrowCancelled, EscrowCreated, EscrowDeclined, EscrowDeliveryAttested, EscrowExpired, EscrowExpiryExtended, EscrowFunded,
+    EscrowRefunded, EscrowReleased, EscrowSecretRevealed, EscrowShipped, ReferralPaid,
+};
+use super::bond::BOND_WITHDRAW_DELAY_DEFAULT;
+use super::shared::{compute_fee, verify_ed25519_signature};
 
 pub const FEE_BPS_DEFAULT: u16 = 200; // 2%
+pub const REVIEW_PERIOD_DEFAULT: i64 = 7 * 24 * 60 * 60; // 7 days
+pub const MAX_ESCROW_DURATION_DEFAULT: i64 = 90 * 24 * 60 * 60; // 90 days
+pub const ORDER_INTENT_PREFIX: &[u8] = b"solmarket:order-intent";
+pub const DELIVERED: &[u8] = b"delivered";
+pub const CRANK_BOUNTY_DEFAULT: u64 = 10_000; // covers a couple of transaction fees
+
+// Bytes the order signer signs off-chain: prefix, order_id, buyer, merchant, amount, expiry, nonce
+pub fn order_intent_message(
+    order_id: u64,
+    buyer: &Pubkey,
+    merchant: &Pubkey,
+    amount_lamports: u64,
+    expires_at: i64,
+    nonce: u64,
+) -> Vec<u8> {
+    let mut message = Vec::with_capacity(ORDER_INTENT_PREFIX.len() + 96);
+    message.extend_from_slice(ORDER_INTENT_PREFIX);
+    message.extend_from_slice(&order_id.to_le_bytes());
+    message.extend_from_slice(buyer.as_ref());
+    message.extend_from_slice(merchant.as_ref());
+    message.extend_from_slice(&amount_lamports.to_le_bytes());
+    message.extend_from_slice(&expires_at.to_le_bytes());
+    message.extend_from_slice(&nonce.to_le_bytes());
+    message
+}
+
+// Bytes the attester signs once an order is delivered: escrow PDA, order_id, created_at,
+// amount, "delivered". created_at and amount tie it to one escrow, not to whatever is
+// re-created at the same PDA once the order closes.
+pub fn delivery_attestation_message(escrow: &Pubkey, order_id: u64, created_at: i64, amount_lamports: u64) -> Vec<u8> {
+    let mut message = Vec::with_capacity(56 + DELIVERED.len());
+    message.extend_from_slice(escrow.as_ref());
+    message.extend_from_slice(&order_id.to_le_bytes());
+    message.extend_from_slice(&created_at.to_le_bytes());
+    message.extend_from_slice(&amount_lamports.to_le_bytes());
+    message.extend_from_slice(DELIVERED);
+    message
+}
+
+// Move lamports out of an escrow PDA. The escrow is owned by this program and
+// carries data, so the system program cannot debit it; adjust balances directly.
+pub fn pay_from_escrow<'info>(
+    escrow: &impl Lamports<'info>,
+    to: &impl Lamports<'info>,
+    amount: u64,
+) -> Result<()> {
+    if amount > 0 {
+        escrow.sub_lamports(amount)?;
+        to.add_lamports(amount)?;
+    }
+    Ok(())
+}
+
+// Take a settled escrow off its buyer's exposure, if it was counted there
+pub fn settle_exposure(escrow: &SolEscrow, buyer_stats: &mut Option<Account<'_, BuyerStats>>) -> Result<()> {
+    if escrow.tracked_in_buyer_stats {
+        let stats = buyer_stats.as_mut().ok_or(EscrowError::BuyerStatsMissing)?;
+        stats.record_settled(escrow.amount_lamports);
+    }
+    Ok(())
+}
+
+// Pay a settlement's fee: the referrer's share accrues to its Referrer account, the
+// rest to the treasury, which applies any fee splits at withdrawal. Both are already
+// rent-exempt, so a small share never fails settlement. Returns (platform_fee, referral_fee).
+pub fn pay_fee<'info>(
+    from: &impl Lamports<'info>,
+    referral_bps: u16,
+    treasury: &Account<'info, Treasury>,
+    referrer: &mut Option<Account<'info, Referrer>>,
+    fee: u64,
+) -> Result<(u64, u64)> {
+    let referral_fee = compute_fee(fee, referral_bps);
+    if referral_fee > 0 {
+        let referrer = referrer.as_mut().ok_or(EscrowError::ReferrerMissing)?;
+        pay_from_escrow(from, &*referrer, referral_fee)?;
+        referrer.record_referral(referral_fee);
+    }
+    let platform_fee = fee - referral_fee;
+    pay_from_escrow(from, treasury, platform_fee)?;
+    Ok((platform_fee, referral_fee))
+}
+
+// Before expiry the merchant releases. During the grace window that follows,
+// release is the default and anyone may trigger it; once the window closes only
+// refunds remain. The platform authority may act at any time.
+// Shipped escrows are released by the buyer confirming delivery, or by anyone once
+// the review period after shipped_at has passed.
+// Hash-locked escrows release through release_with_secret; only the authority may skip it.
+pub fn release_allowed(escrow: &SolEscrow, config: &Config, signer: Pubkey, now: i64) -> bool {
+    if escrow.is_hash_locked() {
+        return signer == config.authority;
+    }
+    if escrow.status == EscrowStatus::Shipped as u8 {
+        let review_end = escrow.shipped_at.saturating_add(config.review_period_secs);
+        return signer == config.authority || now >= review_end;
+    }
+    release_window_allowed(escrow.merchant, escrow.expires_at, config, signer, now)
+}
+
+pub fn refund_allowed(escrow: &SolEscrow, config: &Config, signer: Pubkey, now: i64) -> bool {
+    // The merchant has until expires_at to reveal; the buyer cannot pull funds out from under them.
+    // Checked before Shipped, since past expires_at the secret can no longer release the funds.
+    if escrow.is_hash_locked() {
+        return signer == config.authority || now >= escrow.expires_at;
+    }
+    // Once shipped the buyer has to raise a dispute instead
+    if escrow.status == EscrowStatus::Shipped as u8 {
+        return signer == config.authority;
+    }
+    refund_window_allowed(escrow.buyer, escrow.expires_at, config, signer, now)
+}
+
+// The expiry rules on their own, shared with token escrows
+pub fn release_window_allowed(merchant: Pubkey, expires_at: i64, config: &Config, signer: Pubkey, now: i64) -> bool {
+    let grace_end = expires_at.saturating_add(config.expiry_grace_secs);
+    signer == config.authority || (now < grace_end && (signer == merchant || now >= expires_at))
+}
+
+pub fn refund_window_allowed(buyer: Pubkey, expires_at: i64, config: &Config, signer: Pubkey, now: i64) -> bool {
+    let grace_end = expires_at.saturating_add(config.expiry_grace_secs);
+    signer == config.authority || (signer == buyer && now < expires_at) || now >= grace_end
+}
 
 #[derive(Accounts)]
 pub struct InitializeConfig<'info> {
@@ -29,11 +162,34 @@ pub fn initialize_config(ctx: Context<InitializeConfig>) -> Result<()> {
     config.fee_bps = FEE_BPS_DEFAULT;
     config.fee_recipient = ctx.accounts.fee_recipient.key();
     config.bump = bump;
+    config.pending_authority = Pubkey::default();
+    config.arbiter = ctx.accounts.authority.key();
+    config.expiry_grace_secs = 0;
+    config.review_period_secs = REVIEW_PERIOD_DEFAULT;
+    config.paused = 0;
+    config.version = CONFIG_VERSION;
+    config.max_escrow_duration_secs = MAX_ESCROW_DURATION_DEFAULT;
+    config.order_signer = Pubkey::default();
+    config.max_referral_bps = 0;
+    config.fee_splits = [FeeSplit::default(); MAX_FEE_SPLITS];
+    config.fee_split_count = 0;
+    config.bond_mint = Pubkey::default();
+    config.bond_token_rate = 0;
+    config.min_bond_bps = 0;
+    config.bond_withdraw_delay_secs = BOND_WITHDRAW_DELAY_DEFAULT;
+    config.attester = Pubkey::default();
+    config.crank_bounty_lamports = CRANK_BOUNTY_DEFAULT;
+    config.min_order_lamports = 0;
+    config.max_order_lamports = 0;
+    config.max_open_escrows_per_buyer = 0;
+    config.max_buyer_exposure_lamports = 0;
+    config.require_verified_merchants = false;
     Ok(())
 }
 
+#[event_cpi]
 #[derive(Accounts)]
-#[instruction(order_id: u64, amount_lamports: u64, expires_at: i64)]
+#[instruction(order_id: u64, amount_lamports: u64, expires_at: i64, milestones: Vec<u64>, intent_nonce: u64)]
 pub struct InitializeEscrow<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
@@ -45,8 +201,37 @@ pub struct InitializeEscrow<'info> {
     pub config: Account<'info, Config>,
     /// CHECK: Buyer paying for the order; often equals payer
     pub buyer: UncheckedAccount<'info>,
-    /// CHECK: Merchant receiving funds
-    pub merchant: UncheckedAccount<'info>,
+    #[account(
+        seeds = [b"merchant", merchant.wallet.as_ref()],
+        bump = merchant.bump,
+        constraint = !merchant.suspended @ EscrowError::MerchantSuspended,
+        constraint = merchant.verified || !config.require_verified_merchants @ EscrowError::MerchantNotVerified
+    )]
+    pub merchant: Account<'info, Merchant>,
+    /// CHECK: instructions sysvar, required once Config.order_signer is set
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: Option<UncheckedAccount<'info>>,
+    /// Registered affiliate who earns its referral_bps of the fee at release
+    #[account(
+        seeds = [b"referrer", referrer.wallet.as_ref()],
+        bump = referrer.bump
+    )]
+    pub referrer: Option<Account<'info, Referrer>>,
+    /// Merchant's bond, required once Config.min_bond_bps is set
+    #[account(
+        seeds = [b"merchant-bond", merchant.wallet.as_ref()],
+        bump = bond.bump
+    )]
+    pub bond: Option<Account<'info, MerchantBond>>,
+    /// Consumes the signed intent's nonce, required once Config.order_signer is set
+    #[account(
+        init,
+        payer = payer,
+        space = OrderIntent::DISCRIMINATOR.len() + OrderIntent::INIT_SPACE,
+        seeds = [b"order-intent", buyer.key().as_ref(), &intent_nonce.to_le_bytes()],
+        bump
+    )]
+    pub intent: Option<Account<'info, OrderIntent>>,
     #[account(
         init,
         payer = payer,
@@ -57,34 +242,142 @@ pub struct InitializeEscrow<'info> {
     pub escrow: Account<'info, SolEscrow>,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn initialize_escrow(
     ctx: Context<InitializeEscrow>,
     order_id: u64,
     amount_lamports: u64,
     expires_at: i64,
+    milestones: Vec<u64>,
+    intent_nonce: u64,
+    hash_lock: [u8; 32],
 ) -> Result<()> {
+    require!(!ctx.accounts.config.is_paused(PAUSE_CREATE), EscrowError::Paused);
     require!(amount_lamports > 0, EscrowError::InvalidAmount);
     let now = Clock::get()?.unix_timestamp;
     require!(expires_at > now, EscrowError::InvalidExpiry);
 
+    let config = &ctx.accounts.config;
+    require!(amount_lamports >= config.min_order_lamports, EscrowError::AmountBelowMinimum);
+    require!(
+        config.max_order_lamports == 0 || amount_lamports <= config.max_order_lamports,
+        EscrowError::AmountAboveMaximum
+    );
+    require!(expires_at - now <= config.max_escrow_duration_secs, EscrowError::DurationTooLong);
+
+    // With an order signer configured, the terms must match an intent it signed
+    let order_signer = ctx.accounts.config.order_signer;
+    if order_signer != Pubkey::default() {
+        let instructions = ctx.accounts.instructions.as_ref().ok_or(EscrowError::InvalidSignature)?;
+        let message = order_intent_message(
+            order_id,
+            &ctx.accounts.buyer.key(),
+            &ctx.accounts.merchant.wallet,
+            amount_lamports,
+            expires_at,
+            intent_nonce,
+        );
+        verify_ed25519_signature(instructions, &order_signer, &message)?;
+
+        // Creating the nonce's record fails if it already exists, so each intent opens one escrow
+        let intent = ctx.accounts.intent.as_mut().ok_or(EscrowError::IntentNonceMissing)?;
+        intent.set_inner(OrderIntent {
+            buyer: ctx.accounts.buyer.key(),
+            nonce: intent_nonce,
+            payer: ctx.accounts.payer.key(),
+            expires_at,
+            bump: ctx.bumps.intent.ok_or(EscrowError::IntentNonceMissing)?,
+        });
+    }
+
+    // The merchant must have at least min_bond_bps of the order bonded
+    let min_bond_bps = ctx.accounts.config.min_bond_bps;
+    if min_bond_bps > 0 {
+        let bond = ctx.accounts.bond.as_ref().ok_or(EscrowError::BondTooLow)?;
+        let required = compute_fee(amount_lamports, min_bond_bps);
+        let value = bond.value_lamports(&ctx.accounts.config.bond_mint, ctx.accounts.config.bond_token_rate);
+        require!(value >= required, EscrowError::BondTooLow);
+    }
+
+    // The share comes from the referrer's registration, capped by the current maximum.
+    // Neither side of the order can refer it and take a rebate.
+    let (referrer, referral_bps) = match &ctx.accounts.referrer {
+        Some(referrer) => {
+            require_keys_neq!(referrer.wallet, ctx.accounts.buyer.key(), EscrowError::SelfReferral);
+            require_keys_neq!(referrer.wallet, ctx.accounts.payer.key(), EscrowError::SelfReferral);
+            (referrer.wallet, referrer.referral_bps.min(ctx.accounts.config.max_referral_bps))
+        }
+        None => (Pubkey::default(), 0),
+    };
+
+    // An empty schedule means a single full release; hash-locked escrows always release in full
+    require!(milestones.len() <= MAX_MILESTONES, EscrowError::InvalidMilestones);
+    require!(milestones.is_empty() || hash_lock == [0u8; 32], EscrowError::InvalidMilestones);
+    require!(milestones.iter().all(|m| *m > 0), EscrowError::InvalidMilestones);
+    if !milestones.is_empty() {
+        let sum = milestones.iter().try_fold(0u64, |acc, m| acc.checked_add(*m));
+        require!(sum == Some(amount_lamports), EscrowError::InvalidMilestones);
+    }
+    let mut schedule = [0u64; MAX_MILESTONES];
+    schedule[..milestones.len()].copy_from_slice(&milestones);
+
     let escrow = &mut ctx.accounts.escrow;
     escrow.order_id = order_id;
     escrow.buyer = ctx.accounts.buyer.key();
-    escrow.merchant = ctx.accounts.merchant.key();
+    escrow.payer = ctx.accounts.payer.key();
+    escrow.merchant = ctx.accounts.merchant.wallet;
     escrow.amount_lamports = amount_lamports;
-    escrow.fee_bps = ctx.accounts.config.fee_bps;
+    escrow.fee_bps = ctx.accounts.merchant.fee_bps(ctx.accounts.config.fee_bps);
     escrow.status = EscrowStatus::Pending as u8;
     escrow.bump = ctx.bumps.escrow;
     escrow.created_at = now;
     escrow.expires_at = expires_at;
+    escrow.milestones = schedule;
+    escrow.milestone_count = milestones.len() as u8;
+    escrow.milestones_paid = 0;
+    escrow.released_lamports = 0;
+    escrow.refunded_lamports = 0;
+    escrow.fulfilment_hash = [0u8; 32];
+    escrow.shipped_at = 0;
+    escrow.version = SOL_ESCROW_VERSION;
+    escrow.intent_nonce = intent_nonce;
+    escrow.referrer = referrer;
+    escrow.referral_bps = referral_bps;
+    escrow.hash_lock = hash_lock;
+    // Counted against the buyer's caps once they fund it, not before
+    escrow.tracked_in_buyer_stats = false;
+
+    emit_cpi!(EscrowCreated {
+        order_id,
+        buyer: escrow.buyer,
+        merchant: escrow.merchant,
+        amount_lamports,
+        fee_bps: escrow.fee_bps,
+        created_at: now,
+        expires_at,
+        intent_nonce,
+    });
     Ok(())
 }
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct FundEscrow<'info> {
     #[account(mut)]
     pub buyer: Signer<'info>,
     pub system_program: Program<'info, System>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+    /// Buyer's exposure stats, required once Config buyer caps are set
+    #[account(
+        mut,
+        seeds = [b"buyer-stats", escrow.buyer.as_ref()],
+        bump = buyer_stats.bump
+    )]
+    pub buyer_stats: Option<Account<'info, BuyerStats>>,
     #[account(
         mut,
         seeds = [b"sol-escrow", escrow.buyer.as_ref(), &escrow.order_id.to_le_bytes()],
@@ -94,20 +387,57 @@ pub struct FundEscrow<'info> {
 }
 
 pub fn fund_escrow(ctx: Context<FundEscrow>) -> Result<()> {
+    require!(!ctx.accounts.config.is_paused(PAUSE_FUND), EscrowError::Paused);
     require!(ctx.accounts.escrow.status == EscrowStatus::Pending as u8, EscrowError::InvalidState);
+
+    // Exposure is counted here rather than at creation, which only the payer signs, so
+    // that nobody but the buyer can use up their caps. Stats are optional until a buyer
+    // cap is set; whenever given, the escrow counts against them.
+    let config = &ctx.accounts.config;
+    let amount = ctx.accounts.escrow.amount_lamports;
+    let tracked = match &mut ctx.accounts.buyer_stats {
+        Some(stats) => {
+            require_keys_eq!(ctx.accounts.buyer.key(), ctx.accounts.escrow.buyer, EscrowError::Unauthorized);
+            require!(
+                config.max_open_escrows_per_buyer == 0 || stats.open_escrows < config.max_open_escrows_per_buyer,
+                EscrowError::TooManyOpenEscrows
+            );
+            let in_flight = stats.lamports_in_flight.saturating_add(amount);
+            require!(
+                config.max_buyer_exposure_lamports == 0 || in_flight <= config.max_buyer_exposure_lamports,
+                EscrowError::ExposureLimitExceeded
+            );
+            stats.record_open(amount);
+            true
+        }
+        None => {
+            require!(!config.has_buyer_caps(), EscrowError::BuyerStatsMissing);
+            false
+        }
+    };
+
     // Transfer SOL from buyer to escrow PDA
     let ix = system_program::Transfer {
         from: ctx.accounts.buyer.to_account_info(),
         to: ctx.accounts.escrow.to_account_info(),
     };
     let cpi = CpiContext::new(ctx.accounts.system_program.to_account_info(), ix);
-    let amount = ctx.accounts.escrow.amount_lamports;
     system_program::transfer(cpi, amount)?;
     let escrow = &mut ctx.accounts.escrow;
     escrow.status = EscrowStatus::Funded as u8;
+    escrow.tracked_in_buyer_stats = tracked;
+
+    emit_cpi!(EscrowFunded {
+        order_id: escrow.order_id,
+        buyer: escrow.buyer,
+        merchant: escrow.merchant,
+        amount_lamports: amount,
+        funded_at: Clock::get()?.unix_timestamp,
+    });
     Ok(())
 }
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct ReleaseEscrow<'info> {
     /// Merchant or authority may release
@@ -119,15 +449,40 @@ pub struct ReleaseEscrow<'info> {
         bump = config.bump
     )]
     pub config: Account<'info, Config>,
-    /// CHECK: payout recipient (merchant)
-    #[account(mut)]
-    pub merchant: UncheckedAccount<'info>,
-    /// CHECK: fee recipient
-    #[account(mut, address = config.fee_recipient)]
-    pub fee_recipient: UncheckedAccount<'info>,
+    #[account(
+        seeds = [b"merchant", escrow.merchant.as_ref()],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, Merchant>,
+    /// CHECK: merchant's registered payout wallet
+    #[account(mut, address = merchant_account.payout_wallet)]
+    pub payout_wallet: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump = treasury.bump
+    )]
+    pub treasury: Account<'info, Treasury>,
+    /// The escrow's referrer; required when it has one
+    #[account(
+        mut,
+        seeds = [b"referrer", escrow.referrer.as_ref()],
+        bump = referrer.bump
+    )]
+    pub referrer: Option<Account<'info, Referrer>>,
+    /// CHECK: instructions sysvar, only needed for attested releases
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: Option<UncheckedAccount<'info>>,
+    /// Buyer's exposure stats; required when the escrow is tracked in them
     #[account(
         mut,
-        close = merchant,
+        seeds = [b"buyer-stats", escrow.buyer.as_ref()],
+        bump = buyer_stats.bump
+    )]
+    pub buyer_stats: Option<Account<'info, BuyerStats>>,
+    #[account(
+        mut,
+        close = payout_wallet,
         seeds = [b"sol-escrow", escrow.buyer.as_ref(), &escrow.order_id.to_le_bytes()],
         bump = escrow.bump
     )]
@@ -135,42 +490,248 @@ pub struct ReleaseEscrow<'info> {
 }
 
 pub fn release_escrow(ctx: Context<ReleaseEscrow>) -> Result<()> {
-    require!(ctx.accounts.escrow.status == EscrowStatus::Funded as u8, EscrowError::InvalidState);
+    require!(!ctx.accounts.config.is_paused(PAUSE_RELEASE), EscrowError::Paused);
+    let status = ctx.accounts.escrow.status;
+    require!(status == EscrowStatus::Funded as u8 || status == EscrowStatus::Shipped as u8, EscrowError::InvalidState);
     let now = Clock::get()?.unix_timestamp;
-    // Allow release by merchant, or by config authority (platform), or after expiry
-    let is_merchant = ctx.accounts.authority.key() == ctx.accounts.escrow.merchant;
-    let is_authority = ctx.accounts.authority.key() == ctx.accounts.config.authority;
-    require!(is_merchant || is_authority || now >= ctx.accounts.escrow.expires_at, EscrowError::Unauthorized);
+    require!(
+        release_allowed(&ctx.accounts.escrow, &ctx.accounts.config, ctx.accounts.authority.key(), now),
+        EscrowError::Unauthorized
+    );
+    pay_out_release(ctx)
+}
+
+// Buyer confirms a shipped order arrived, which releases it to the merchant
+pub fn confirm_delivery(ctx: Context<ReleaseEscrow>) -> Result<()> {
+    require!(!ctx.accounts.config.is_paused(PAUSE_RELEASE), EscrowError::Paused);
+    require!(ctx.accounts.escrow.status == EscrowStatus::Shipped as u8, EscrowError::InvalidState);
+    require_keys_eq!(ctx.accounts.authority.key(), ctx.accounts.escrow.buyer, EscrowError::Unauthorized);
+    pay_out_release(ctx)
+}
 
-    let total = ctx.accounts.escrow.amount_lamports as u128;
-    let fee = total * (ctx.accounts.escrow.fee_bps as u128) / 10_000;
+// Anyone holding the merchant's secret releases a hash-locked escrow; revealing it
+// on-chain is what delivers it to the buyer
+pub fn release_with_secret(ctx: Context<ReleaseEscrow>, secret: Vec<u8>) -> Result<()> {
+    require!(!ctx.accounts.config.is_paused(PAUSE_RELEASE), EscrowError::Paused);
+    let escrow = &ctx.accounts.escrow;
+    require!(escrow.is_hash_locked(), EscrowError::InvalidState);
+    let status = escrow.status;
+    require!(status == EscrowStatus::Funded as u8 || status == EscrowStatus::Shipped as u8, EscrowError::InvalidState);
+    let now = Clock::get()?.unix_timestamp;
+    require!(now < escrow.expires_at, EscrowError::Expired);
+    require!(hash::hash(&secret).to_bytes() == escrow.hash_lock, EscrowError::InvalidSecret);
+
+    emit_cpi!(EscrowSecretRevealed {
+        order_id: escrow.order_id,
+        buyer: escrow.buyer,
+        merchant: escrow.merchant,
+        secret,
+        revealed_at: now,
+    });
+    pay_out_release(ctx)
+}
+
+// Anyone may release once the delivery attester has signed off on this escrow; the
+// Ed25519 program instruction carrying the attestation must directly precede this one
+pub fn release_with_attestation(ctx: Context<ReleaseEscrow>) -> Result<()> {
+    require!(!ctx.accounts.config.is_paused(PAUSE_RELEASE), EscrowError::Paused);
+    let escrow = &ctx.accounts.escrow;
+    let status = escrow.status;
+    require!(status == EscrowStatus::Funded as u8 || status == EscrowStatus::Shipped as u8, EscrowError::InvalidState);
+    // An attestation does not stand in for the merchant's secret
+    require!(!escrow.is_hash_locked(), EscrowError::InvalidState);
+
+    let attester = ctx.accounts.config.attester;
+    require!(attester != Pubkey::default(), EscrowError::InvalidSignature);
+    let instructions = ctx.accounts.instructions.as_ref().ok_or(EscrowError::InvalidSignature)?;
+    let message = delivery_attestation_message(&escrow.key(), escrow.order_id, escrow.created_at, escrow.amount_lamports);
+    verify_ed25519_signature(instructions, &attester, &message)?;
+
+    emit_cpi!(EscrowDeliveryAttested {
+        order_id: escrow.order_id,
+        buyer: escrow.buyer,
+        merchant: escrow.merchant,
+        attester,
+        attested_at: Clock::get()?.unix_timestamp,
+    });
+    pay_out_release(ctx)
+}
+
+fn pay_out_release(ctx: Context<ReleaseEscrow>) -> Result<()> {
+    // Pays whatever milestones have not been released yet
+    let total = ctx.accounts.escrow.remaining_lamports();
+    let fee = compute_fee(total, ctx.accounts.escrow.fee_bps);
     let pay_merchant = total - fee;
 
-    // Transfer from escrow PDA → merchant and fee_recipient
-    let order_id_bytes = ctx.accounts.escrow.order_id.to_le_bytes();
-    let seeds: [&[u8]; 4] = [b"sol-escrow", ctx.accounts.escrow.buyer.as_ref(), &order_id_bytes, &[ctx.accounts.escrow.bump]];
-    let signer_seeds = [&seeds[..]];
-    let ix_m = system_program::Transfer {
-        from: ctx.accounts.escrow.to_account_info(),
-        to: ctx.accounts.merchant.to_account_info(),
-    };
-    let cpi_m = CpiContext::new_with_signer(ctx.accounts.system_program.to_account_info(), ix_m, &signer_seeds);
-    system_program::transfer(cpi_m, pay_merchant as u64)?;
-
-    if fee > 0 {
-        let ix_f = system_program::Transfer {
-            from: ctx.accounts.escrow.to_account_info(),
-            to: ctx.accounts.fee_recipient.to_account_info(),
-        };
-        let cpi_f = CpiContext::new_with_signer(ctx.accounts.system_program.to_account_info(), ix_f, &signer_seeds);
-        system_program::transfer(cpi_f, fee as u64)?;
-    }
+    // Transfer from escrow PDA → merchant's payout wallet, fee treasury and referrer
+    pay_from_escrow(&ctx.accounts.escrow, &ctx.accounts.payout_wallet, pay_merchant)?;
+    let referral_bps = ctx.accounts.escrow.referral_bps;
+    let (platform_fee, referral_fee) =
+        pay_fee(&ctx.accounts.escrow, referral_bps, &ctx.accounts.treasury, &mut ctx.accounts.referrer, fee)?;
+    ctx.accounts.treasury.record_release(total, platform_fee, true);
+    settle_exposure(&ctx.accounts.escrow, &mut ctx.accounts.buyer_stats)?;
 
     let escrow = &mut ctx.accounts.escrow;
     escrow.status = EscrowStatus::Released as u8;
+    if referral_fee > 0 {
+        emit_cpi!(ReferralPaid {
+            order_id: escrow.order_id,
+            referrer: escrow.referrer,
+            fee,
+            platform_fee,
+            referral_fee,
+        });
+    }
+
+    emit_cpi!(EscrowReleased {
+        order_id: escrow.order_id,
+        buyer: escrow.buyer,
+        merchant: escrow.merchant,
+        merchant_payout: pay_merchant,
+        fee,
+        fully_released: true,
+        released_at: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct MarkShipped<'info> {
+    pub merchant: Signer<'info>,
+    #[account(
+        mut,
+        has_one = merchant @ EscrowError::Unauthorized,
+        seeds = [b"sol-escrow", escrow.buyer.as_ref(), &escrow.order_id.to_le_bytes()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, SolEscrow>,
+}
+
+// Merchant records fulfilment, e.g. a tracking number hash or digital delivery receipt
+pub fn mark_shipped(ctx: Context<MarkShipped>, fulfilment_hash: [u8; 32]) -> Result<()> {
+    require!(ctx.accounts.escrow.status == EscrowStatus::Funded as u8, EscrowError::InvalidState);
+    // Revealing the secret is how a hash-locked order proves delivery; there is no review period
+    require!(!ctx.accounts.escrow.is_hash_locked(), EscrowError::InvalidState);
+    let now = Clock::get()?.unix_timestamp;
+    // Shipping after expiry would pull the escrow out of the buyer's refund path
+    require!(now < ctx.accounts.escrow.expires_at, EscrowError::Expired);
+    let escrow = &mut ctx.accounts.escrow;
+    escrow.fulfilment_hash = fulfilment_hash;
+    escrow.shipped_at = now;
+    escrow.status = EscrowStatus::Shipped as u8;
+
+    emit_cpi!(EscrowShipped {
+        order_id: escrow.order_id,
+        buyer: escrow.buyer,
+        merchant: escrow.merchant,
+        fulfilment_hash,
+        shipped_at: escrow.shipped_at,
+    });
     Ok(())
 }
 
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ReleaseMilestone<'info> {
+    /// Merchant or authority may release
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"merchant", escrow.merchant.as_ref()],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, Merchant>,
+    /// CHECK: merchant's registered payout wallet
+    #[account(mut, address = merchant_account.payout_wallet)]
+    pub payout_wallet: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump = treasury.bump
+    )]
+    pub treasury: Account<'info, Treasury>,
+    /// The escrow's referrer; required when it has one
+    #[account(
+        mut,
+        seeds = [b"referrer", escrow.referrer.as_ref()],
+        bump = referrer.bump
+    )]
+    pub referrer: Option<Account<'info, Referrer>>,
+    /// Buyer's exposure stats; required when the escrow is tracked in them
+    #[account(
+        mut,
+        seeds = [b"buyer-stats", escrow.buyer.as_ref()],
+        bump = buyer_stats.bump
+    )]
+    pub buyer_stats: Option<Account<'info, BuyerStats>>,
+    #[account(
+        mut,
+        seeds = [b"sol-escrow", escrow.buyer.as_ref(), &escrow.order_id.to_le_bytes()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, SolEscrow>,
+}
+
+// Pay the next milestone minus the fee; closes the escrow once the last one is paid
+pub fn release_milestone(ctx: Context<ReleaseMilestone>) -> Result<()> {
+    require!(!ctx.accounts.config.is_paused(PAUSE_RELEASE), EscrowError::Paused);
+    require!(ctx.accounts.escrow.status == EscrowStatus::Funded as u8, EscrowError::InvalidState);
+    let now = Clock::get()?.unix_timestamp;
+    require!(
+        release_allowed(&ctx.accounts.escrow, &ctx.accounts.config, ctx.accounts.authority.key(), now),
+        EscrowError::Unauthorized
+    );
+
+    let paid = ctx.accounts.escrow.milestones_paid as usize;
+    require!(paid < ctx.accounts.escrow.milestone_count as usize, EscrowError::InvalidMilestones);
+    // Partial refunds come out of the tail of the schedule
+    let amount = ctx.accounts.escrow.milestones[paid].min(ctx.accounts.escrow.remaining_lamports());
+    let fee = compute_fee(amount, ctx.accounts.escrow.fee_bps);
+
+    pay_from_escrow(&ctx.accounts.escrow, &ctx.accounts.payout_wallet, amount - fee)?;
+    let referral_bps = ctx.accounts.escrow.referral_bps;
+    let (platform_fee, referral_fee) =
+        pay_fee(&ctx.accounts.escrow, referral_bps, &ctx.accounts.treasury, &mut ctx.accounts.referrer, fee)?;
+
+    let escrow = &mut ctx.accounts.escrow;
+    escrow.milestones_paid += 1;
+    escrow.released_lamports += amount;
+    let fully_released = escrow.milestones_paid == escrow.milestone_count || escrow.remaining_lamports() == 0;
+    ctx.accounts.treasury.record_release(amount, platform_fee, fully_released);
+
+    if referral_fee > 0 {
+        emit_cpi!(ReferralPaid {
+            order_id: escrow.order_id,
+            referrer: escrow.referrer,
+            fee,
+            platform_fee,
+            referral_fee,
+        });
+    }
+    emit_cpi!(EscrowReleased {
+        order_id: escrow.order_id,
+        buyer: escrow.buyer,
+        merchant: escrow.merchant,
+        merchant_payout: amount - fee,
+        fee,
+        fully_released,
+        released_at: now,
+    });
+    if fully_released {
+        settle_exposure(escrow, &mut ctx.accounts.buyer_stats)?;
+        escrow.status = EscrowStatus::Released as u8;
+        escrow.close(ctx.accounts.payout_wallet.to_account_info())?;
+    }
+    Ok(())
+}
+
+#[event_cpi]
 #[derive(Accounts)]
 pub struct RefundEscrow<'info> {
     /// Buyer or authority may refund
@@ -183,8 +744,15 @@ pub struct RefundEscrow<'info> {
     )]
     pub config: Account<'info, Config>,
     /// CHECK: refund recipient (buyer)
-    #[account(mut)]
+    #[account(mut, address = escrow.buyer)]
     pub buyer_account: UncheckedAccount<'info>,
+    /// Buyer's exposure stats; required when the escrow is tracked in them
+    #[account(
+        mut,
+        seeds = [b"buyer-stats", escrow.buyer.as_ref()],
+        bump = buyer_stats.bump
+    )]
+    pub buyer_stats: Option<Account<'info, BuyerStats>>,
     #[account(
         mut,
         close = buyer_account,
@@ -195,29 +763,273 @@ pub struct RefundEscrow<'info> {
 }
 
 pub fn refund_escrow(ctx: Context<RefundEscrow>) -> Result<()> {
+    // PAUSE_REFUND holds back the authority and post-grace callers; the buyer can still cancel
+    require!(
+        ctx.accounts.authority.key() == ctx.accounts.escrow.buyer || !ctx.accounts.config.is_paused(PAUSE_REFUND),
+        EscrowError::Paused
+    );
     let status = ctx.accounts.escrow.status;
-    require!(status == EscrowStatus::Funded as u8 || status == EscrowStatus::Pending as u8, EscrowError::InvalidState);
+    require!(
+        status == EscrowStatus::Funded as u8 || status == EscrowStatus::Pending as u8 || status == EscrowStatus::Shipped as u8,
+        EscrowError::InvalidState
+    );
     let now = Clock::get()?.unix_timestamp;
-    let is_buyer = ctx.accounts.authority.key() == ctx.accounts.escrow.buyer;
+    require!(
+        refund_allowed(&ctx.accounts.escrow, &ctx.accounts.config, ctx.accounts.authority.key(), now),
+        EscrowError::Unauthorized
+    );
+
+    // Transfer from escrow PDA → buyer; milestones already released stay with the merchant
+    let amount = ctx.accounts.escrow.refundable_lamports();
+    pay_from_escrow(&ctx.accounts.escrow, &ctx.accounts.buyer_account, amount)?;
+    settle_exposure(&ctx.accounts.escrow, &mut ctx.accounts.buyer_stats)?;
+
+    let escrow = &mut ctx.accounts.escrow;
+    escrow.status = EscrowStatus::Refunded as u8;
+
+    emit_cpi!(EscrowRefunded {
+        order_id: escrow.order_id,
+        buyer: escrow.buyer,
+        merchant: escrow.merchant,
+        refunded_lamports: amount,
+        partial: false,
+        refunded_at: now,
+    });
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct PartialRefund<'info> {
+    /// Merchant or authority may partially refund
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+    /// CHECK: refund recipient (buyer)
+    #[account(mut, address = escrow.buyer)]
+    pub buyer_account: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"sol-escrow", escrow.buyer.as_ref(), &escrow.order_id.to_le_bytes()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, SolEscrow>,
+}
+
+// Return part of the escrowed amount to the buyer; the escrow stays Funded for the rest
+pub fn partial_refund(ctx: Context<PartialRefund>, amount: u64) -> Result<()> {
+    require!(!ctx.accounts.config.is_paused(PAUSE_REFUND), EscrowError::Paused);
+    let status = ctx.accounts.escrow.status;
+    require!(status == EscrowStatus::Funded as u8 || status == EscrowStatus::Shipped as u8, EscrowError::InvalidState);
+    let is_merchant = ctx.accounts.authority.key() == ctx.accounts.escrow.merchant;
     let is_authority = ctx.accounts.authority.key() == ctx.accounts.config.authority;
-    require!(is_buyer || is_authority || now >= ctx.accounts.escrow.expires_at, EscrowError::Unauthorized);
-
-    // Transfer from escrow PDA → buyer
-    let order_id_bytes = ctx.accounts.escrow.order_id.to_le_bytes();
-    let seeds: [&[u8]; 4] = [b"sol-escrow", ctx.accounts.escrow.buyer.as_ref(), &order_id_bytes, &[ctx.accounts.escrow.bump]];
-    let signer_seeds = [&seeds[..]];
-    let ix_b = system_program::Transfer {
-        from: ctx.accounts.escrow.to_account_info(),
-        to: ctx.accounts.buyer_account.to_account_info(),
+    require!(is_merchant || is_authority, EscrowError::Unauthorized);
+    // A refund of everything that is left should go through refund_escrow
+    require!(amount > 0 && amount < ctx.accounts.escrow.remaining_lamports(), EscrowError::InvalidAmount);
+
+    pay_from_escrow(&ctx.accounts.escrow, &ctx.accounts.buyer_account, amount)?;
+    let escrow = &mut ctx.accounts.escrow;
+    escrow.refunded_lamports += amount;
+
+    emit_cpi!(EscrowRefunded {
+        order_id: escrow.order_id,
+        buyer: escrow.buyer,
+        merchant: escrow.merchant,
+        refunded_lamports: amount,
+        partial: true,
+        refunded_at: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct DeclineOrder<'info> {
+    pub merchant: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+    /// CHECK: refund recipient (buyer)
+    #[account(mut, address = escrow.buyer)]
+    pub buyer_account: UncheckedAccount<'info>,
+    /// CHECK: rent payer, receives the escrow's rent on close
+    #[account(mut, address = escrow.payer)]
+    pub payer: UncheckedAccount<'info>,
+    /// Buyer's exposure stats; required when the escrow is tracked in them
+    #[account(
+        mut,
+        seeds = [b"buyer-stats", escrow.buyer.as_ref()],
+        bump = buyer_stats.bump
+    )]
+    pub buyer_stats: Option<Account<'info, BuyerStats>>,
+    #[account(
+        mut,
+        close = payer,
+        has_one = merchant @ EscrowError::Unauthorized,
+        seeds = [b"sol-escrow", escrow.buyer.as_ref(), &escrow.order_id.to_le_bytes()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, SolEscrow>,
+}
+
+// Merchant rejects an order they cannot fill; the buyer gets everything back
+pub fn decline_order(ctx: Context<DeclineOrder>, reason_code: u8) -> Result<()> {
+    require!(!ctx.accounts.config.is_paused(PAUSE_REFUND), EscrowError::Paused);
+    let status = ctx.accounts.escrow.status;
+    require!(status == EscrowStatus::Pending as u8 || status == EscrowStatus::Funded as u8, EscrowError::InvalidState);
+
+    let amount = ctx.accounts.escrow.refundable_lamports();
+    pay_from_escrow(&ctx.accounts.escrow, &ctx.accounts.buyer_account, amount)?;
+    settle_exposure(&ctx.accounts.escrow, &mut ctx.accounts.buyer_stats)?;
+
+    let escrow = &mut ctx.accounts.escrow;
+    escrow.status = EscrowStatus::Declined as u8;
+
+    emit_cpi!(EscrowDeclined {
+        order_id: escrow.order_id,
+        buyer: escrow.buyer,
+        merchant: escrow.merchant,
+        reason_code,
+        refunded_lamports: amount,
+        declined_at: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct MutualCancel<'info> {
+    /// Buyer and merchant both sign; the buyer receives the refund
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    pub merchant: Signer<'info>,
+    /// CHECK: rent payer, receives the escrow's rent on close
+    #[account(mut, address = escrow.payer)]
+    pub payer: UncheckedAccount<'info>,
+    /// Buyer's exposure stats; required when the escrow is tracked in them
+    #[account(
+        mut,
+        seeds = [b"buyer-stats", escrow.buyer.as_ref()],
+        bump = buyer_stats.bump
+    )]
+    pub buyer_stats: Option<Account<'info, BuyerStats>>,
+    #[account(
+        mut,
+        close = payer,
+        has_one = buyer @ EscrowError::Unauthorized,
+        has_one = merchant @ EscrowError::Unauthorized,
+        seeds = [b"sol-escrow", escrow.buyer.as_ref(), &escrow.order_id.to_le_bytes()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, SolEscrow>,
+    /// The open dispute; required when cancelling a Disputed escrow
+    #[account(
+        mut,
+        seeds = [b"dispute", escrow.key().as_ref(), &escrow.created_at.to_le_bytes()],
+        bump = dispute.bump
+    )]
+    pub dispute: Option<Account<'info, Dispute>>,
+}
+
+// Both parties agree to call the order off, at any point before release
+pub fn mutual_cancel(ctx: Context<MutualCancel>) -> Result<()> {
+    let previous_status = ctx.accounts.escrow.status;
+    require!(
+        previous_status == EscrowStatus::Pending as u8
+            || previous_status == EscrowStatus::Funded as u8
+            || previous_status == EscrowStatus::Shipped as u8
+            || previous_status == EscrowStatus::Disputed as u8,
+        EscrowError::InvalidState
+    );
+
+    let now = Clock::get()?.unix_timestamp;
+    // Settling it themselves closes the dispute too, with everything back to the buyer
+    if previous_status == EscrowStatus::Disputed as u8 {
+        let dispute = ctx.accounts.dispute.as_mut().ok_or(EscrowError::DisputeMissing)?;
+        dispute.buyer_bps = 10_000;
+        dispute.resolved_at = now;
+    }
+
+    let amount = ctx.accounts.escrow.refundable_lamports();
+    pay_from_escrow(&ctx.accounts.escrow, &ctx.accounts.buyer, amount)?;
+    settle_exposure(&ctx.accounts.escrow, &mut ctx.accounts.buyer_stats)?;
+
+    let escrow = &mut ctx.accounts.escrow;
+    escrow.status = EscrowStatus::Cancelled as u8;
+
+    emit_cpi!(EscrowCancelled {
+        order_id: escrow.order_id,
+        buyer: escrow.buyer,
+        merchant: escrow.merchant,
+        refunded_lamports: amount,
+        previous_status,
+        cancelled_at: now,
+    });
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ExtendExpiry<'info> {
+    /// The buyer always consents; the merchant co-signs when they asked for the extension
+    pub buyer: Signer<'info>,
+    pub merchant: Option<Signer<'info>>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        has_one = buyer @ EscrowError::Unauthorized,
+        seeds = [b"sol-escrow", escrow.buyer.as_ref(), &escrow.order_id.to_le_bytes()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, SolEscrow>,
+}
+
+// Push expires_at out, e.g. for a shipping delay, instead of refunding and re-ordering
+pub fn extend_expiry(ctx: Context<ExtendExpiry>, new_expires_at: i64) -> Result<()> {
+    let status = ctx.accounts.escrow.status;
+    require!(
+        status == EscrowStatus::Pending as u8
+            || status == EscrowStatus::Funded as u8
+            || status == EscrowStatus::Shipped as u8,
+        EscrowError::InvalidState
+    );
+    let requested_by = match &ctx.accounts.merchant {
+        Some(merchant) => {
+            require_keys_eq!(merchant.key(), ctx.accounts.escrow.merchant, EscrowError::Unauthorized);
+            merchant.key()
+        }
+        None => ctx.accounts.buyer.key(),
     };
-    let cpi_b = CpiContext::new_with_signer(ctx.accounts.system_program.to_account_info(), ix_b, &signer_seeds);
-    system_program::transfer(cpi_b, ctx.accounts.escrow.amount_lamports)?;
+
+    let old_expires_at = ctx.accounts.escrow.expires_at;
+    require!(new_expires_at > old_expires_at, EscrowError::InvalidExpiry);
+    let latest = ctx.accounts.escrow.created_at.saturating_add(ctx.accounts.config.max_escrow_duration_secs);
+    require!(new_expires_at <= latest, EscrowError::DurationTooLong);
 
     let escrow = &mut ctx.accounts.escrow;
-    escrow.status = EscrowStatus::Refunded as u8;
+    escrow.expires_at = new_expires_at;
+
+    emit_cpi!(EscrowExpiryExtended {
+        order_id: escrow.order_id,
+        buyer: escrow.buyer,
+        merchant: escrow.merchant,
+        requested_by,
+        old_expires_at,
+        new_expires_at,
+    });
     Ok(())
 }
 
+#[event_cpi]
 #[derive(Accounts)]
 pub struct ExpireEscrow<'info> {
     pub system_program: Program<'info, System>,
@@ -227,30 +1039,135 @@ pub struct ExpireEscrow<'info> {
     )]
     pub config: Account<'info, Config>,
     /// CHECK: refund recipient (buyer)
-    #[account(mut)]
+    #[account(mut, address = escrow.buyer)]
     pub buyer_account: UncheckedAccount<'info>,
+    /// CHECK: rent payer, receives the escrow's rent on close
+    #[account(mut, address = escrow.payer)]
+    pub payer: UncheckedAccount<'info>,
+    /// Buyer's exposure stats; required when the escrow is tracked in them
     #[account(
         mut,
-        close = buyer_account,
+        seeds = [b"buyer-stats", escrow.buyer.as_ref()],
+        bump = buyer_stats.bump
+    )]
+    pub buyer_stats: Option<Account<'info, BuyerStats>>,
+    #[account(
+        mut,
+        close = payer,
         seeds = [b"sol-escrow", escrow.buyer.as_ref(), &escrow.order_id.to_le_bytes()],
         bump = escrow.bump
     )]
     pub escrow: Account<'info, SolEscrow>,
 }
 
+// Permissionless expiry:
+// - Pending escrows only hold rent, which goes back to the payer once expires_at passes
+// - Funded escrows refund the buyer once the release grace window has closed
+// Returns (refunded_lamports, was_funded).
+fn settle_expired<'info>(
+    escrow: &Account<'info, SolEscrow>,
+    config: &Config,
+    buyer_account: &UncheckedAccount<'info>,
+    now: i64,
+) -> Result<(u64, bool)> {
+    let grace_end = escrow.expires_at.saturating_add(config.expiry_grace_secs);
+    let was_funded = escrow.status == EscrowStatus::Funded as u8;
+    if was_funded {
+        require!(now >= grace_end, EscrowError::NotExpired);
+    } else {
+        require!(escrow.status == EscrowStatus::Pending as u8, EscrowError::InvalidState);
+        require!(now >= escrow.expires_at, EscrowError::NotExpired);
+    }
+
+    // use refund logic without signer (escrow is program-owned)
+    let refunded = escrow.refundable_lamports();
+    pay_from_escrow(escrow, buyer_account, refunded)?;
+    Ok((refunded, was_funded))
+}
+
 pub fn expire_escrow(ctx: Context<ExpireEscrow>) -> Result<()> {
+    require!(!ctx.accounts.config.is_paused(PAUSE_REFUND), EscrowError::Paused);
     let now = Clock::get()?.unix_timestamp;
-    require!(now >= ctx.accounts.escrow.expires_at, EscrowError::NotExpired);
-    // use refund logic without signer (escrow signs)
-    let order_id_bytes = ctx.accounts.escrow.order_id.to_le_bytes();
-    let seeds: [&[u8]; 4] = [b"sol-escrow", ctx.accounts.escrow.buyer.as_ref(), &order_id_bytes, &[ctx.accounts.escrow.bump]];
-    let signer_seeds = [&seeds[..]];
-    let ix_b = system_program::Transfer {
-        from: ctx.accounts.escrow.to_account_info(),
-        to: ctx.accounts.buyer_account.to_account_info(),
-    };
-    let cpi_b = CpiContext::new_with_signer(ctx.accounts.system_program.to_account_info(), ix_b, &signer_seeds);
-    system_program::transfer(cpi_b, ctx.accounts.escrow.amount_lamports)?;
+    let (refunded, was_funded) =
+        settle_expired(&ctx.accounts.escrow, &ctx.accounts.config, &ctx.accounts.buyer_account, now)?;
+    settle_exposure(&ctx.accounts.escrow, &mut ctx.accounts.buyer_stats)?;
+
+    let escrow = &mut ctx.accounts.escrow;
+    escrow.status = EscrowStatus::Expired as u8;
+    emit_cpi!(EscrowExpired {
+        order_id: escrow.order_id,
+        buyer: escrow.buyer,
+        merchant: escrow.merchant,
+        refunded_lamports: refunded,
+        was_funded,
+        status: escrow.status,
+        expired_at: now,
+    });
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct CrankExpire<'info> {
+    /// Whoever runs the crank; receives the bounty
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+    /// CHECK: refund recipient (buyer)
+    #[account(mut, address = escrow.buyer)]
+    pub buyer_account: UncheckedAccount<'info>,
+    /// CHECK: rent payer, receives what is left of the escrow's rent on close
+    #[account(mut, address = escrow.payer)]
+    pub payer: UncheckedAccount<'info>,
+    /// Buyer's exposure stats; required when the escrow is tracked in them
+    #[account(
+        mut,
+        seeds = [b"buyer-stats", escrow.buyer.as_ref()],
+        bump = buyer_stats.bump
+    )]
+    pub buyer_stats: Option<Account<'info, BuyerStats>>,
+    #[account(
+        mut,
+        close = payer,
+        seeds = [b"sol-escrow", escrow.buyer.as_ref(), &escrow.order_id.to_le_bytes()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, SolEscrow>,
+}
+
+// expire_escrow with a reward: the caller gets Config.crank_bounty_lamports out of the
+// escrow's rent, after the buyer has been refunded, so the principal is never touched
+pub fn crank_expire(ctx: Context<CrankExpire>) -> Result<()> {
+    require!(!ctx.accounts.config.is_paused(PAUSE_REFUND), EscrowError::Paused);
+    let now = Clock::get()?.unix_timestamp;
+    let (refunded, was_funded) =
+        settle_expired(&ctx.accounts.escrow, &ctx.accounts.config, &ctx.accounts.buyer_account, now)?;
+    settle_exposure(&ctx.accounts.escrow, &mut ctx.accounts.buyer_stats)?;
+
+    let bounty = ctx.accounts.config.crank_bounty_lamports.min(ctx.accounts.escrow.get_lamports());
+    pay_from_escrow(&ctx.accounts.escrow, &ctx.accounts.cranker, bounty)?;
+
+    let escrow = &mut ctx.accounts.escrow;
+    escrow.status = EscrowStatus::Expired as u8;
+    emit_cpi!(EscrowExpired {
+        order_id: escrow.order_id,
+        buyer: escrow.buyer,
+        merchant: escrow.merchant,
+        refunded_lamports: refunded,
+        was_funded,
+        status: escrow.status,
+        expired_at: now,
+    });
+    emit_cpi!(CrankRewarded {
+        order_id: escrow.order_id,
+        cranker: ctx.accounts.cranker.key(),
+        bounty,
+    });
     Ok(())
 }
 
@@ -266,6 +1183,68 @@ pub enum EscrowError {
     Unauthorized,
     #[msg("Not expired")]
     NotExpired,
+    #[msg("Fee exceeds maximum")]
+    FeeTooHigh,
+    #[msg("No pending authority")]
+    NoPendingAuthority,
+    #[msg("Escrow expired")]
+    Expired,
+    #[msg("Invalid basis points")]
+    InvalidBps,
+    #[msg("Invalid milestone schedule")]
+    InvalidMilestones,
+    #[msg("Invalid cart")]
+    InvalidCart,
+    #[msg("Insufficient treasury balance")]
+    InsufficientTreasury,
+    #[msg("Merchant is suspended")]
+    MerchantSuspended,
+    #[msg("Merchant is not registered")]
+    MerchantNotRegistered,
+    #[msg("Paused")]
+    Paused,
+    #[msg("Escrow duration exceeds maximum")]
+    DurationTooLong,
+    #[msg("Missing or invalid Ed25519 signature")]
+    InvalidSignature,
+    #[msg("Referral share exceeds maximum")]
+    ReferralTooHigh,
+    #[msg("Referrer account missing")]
+    ReferrerMissing,
+    #[msg("Buyer or payer cannot refer their own order")]
+    SelfReferral,
+    #[msg("Invalid fee splits")]
+    InvalidFeeSplits,
+    #[msg("Withdrawal destination missing")]
+    DestinationMissing,
+    #[msg("Merchant bond below minimum")]
+    BondTooLow,
+    #[msg("Bond withdrawal is still timelocked")]
+    BondLocked,
+    #[msg("Mint is not the bond mint")]
+    InvalidBondMint,
+    #[msg("Secret does not match the hash lock")]
+    InvalidSecret,
+    #[msg("Invalid batch accounts")]
+    InvalidBatch,
+    #[msg("Mints with a transfer fee are not supported")]
+    TransferFeeMint,
+    #[msg("Amount below the minimum order size")]
+    AmountBelowMinimum,
+    #[msg("Amount above the maximum order size")]
+    AmountAboveMaximum,
+    #[msg("Buyer has too many open escrows")]
+    TooManyOpenEscrows,
+    #[msg("Buyer exposure limit exceeded")]
+    ExposureLimitExceeded,
+    #[msg("Buyer stats account missing")]
+    BuyerStatsMissing,
+    #[msg("Order intent nonce account missing")]
+    IntentNonceMissing,
+    #[msg("Dispute account missing")]
+    DisputeMissing,
+    #[msg("Merchant is not verified")]
+    MerchantNotVerified,
 }
 
 
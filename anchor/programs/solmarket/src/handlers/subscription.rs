@@ -0,0 +1,319 @@
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use anchor_lang::system_program;
+use crate::events::{SubscriptionCancelled, SubscriptionCharged, SubscriptionCreated, SubscriptionFunded};
+use crate::state::{Config, Merchant, Subscription, Treasury, PAUSE_CREATE, PAUSE_FUND, PAUSE_RELEASE};
+use super::shared::compute_fee;
+use super::sol_escrow::{pay_fee, pay_from_escrow, EscrowError};
+
+// Move `periods` worth of lamports from the buyer into the subscription
+fn prepay<'info>(
+    buyer: &Signer<'info>,
+    subscription: &Account<'info, Subscription>,
+    system_program: &Program<'info, System>,
+    periods: u32,
+) -> Result<u64> {
+    require!(periods > 0, EscrowError::InvalidAmount);
+    let amount = subscription
+        .amount_per_period
+        .checked_mul(periods as u64)
+        .ok_or(EscrowError::InvalidAmount)?;
+    let ix = system_program::Transfer {
+        from: buyer.to_account_info(),
+        to: subscription.to_account_info(),
+    };
+    system_program::transfer(CpiContext::new(system_program.to_account_info(), ix), amount)?;
+    Ok(amount)
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(subscription_id: u64)]
+pub struct CreateSubscription<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"merchant", merchant.wallet.as_ref()],
+        bump = merchant.bump,
+        constraint = !merchant.suspended @ EscrowError::MerchantSuspended,
+        constraint = merchant.verified || !config.require_verified_merchants @ EscrowError::MerchantNotVerified
+    )]
+    pub merchant: Account<'info, Merchant>,
+    #[account(
+        init,
+        payer = buyer,
+        space = Subscription::DISCRIMINATOR.len() + Subscription::INIT_SPACE,
+        seeds = [b"subscription", buyer.key().as_ref(), &subscription_id.to_le_bytes()],
+        bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+}
+
+// Buyer opens a subscription and prepays its first `periods` periods
+pub fn create_subscription(
+    ctx: Context<CreateSubscription>,
+    subscription_id: u64,
+    period_secs: i64,
+    amount_per_period: u64,
+    first_charge_at: i64,
+    periods: u32,
+) -> Result<()> {
+    require!(!ctx.accounts.config.is_paused(PAUSE_CREATE), EscrowError::Paused);
+    require!(!ctx.accounts.config.is_paused(PAUSE_FUND), EscrowError::Paused);
+    require!(amount_per_period > 0, EscrowError::InvalidAmount);
+    require!(period_secs > 0, EscrowError::InvalidExpiry);
+    let now = Clock::get()?.unix_timestamp;
+    require!(first_charge_at >= now, EscrowError::InvalidExpiry);
+
+    ctx.accounts.subscription.set_inner(Subscription {
+        subscription_id,
+        buyer: ctx.accounts.buyer.key(),
+        merchant: ctx.accounts.merchant.wallet,
+        period_secs,
+        amount_per_period,
+        fee_bps: ctx.accounts.merchant.fee_bps(ctx.accounts.config.fee_bps),
+        next_charge_at: first_charge_at,
+        prepaid_lamports: 0,
+        periods_charged: 0,
+        created_at: now,
+        bump: ctx.bumps.subscription,
+    });
+    let amount = prepay(
+        &ctx.accounts.buyer,
+        &ctx.accounts.subscription,
+        &ctx.accounts.system_program,
+        periods,
+    )?;
+    let subscription = &mut ctx.accounts.subscription;
+    subscription.prepaid_lamports = amount;
+
+    emit_cpi!(SubscriptionCreated {
+        subscription_id,
+        buyer: subscription.buyer,
+        merchant: subscription.merchant,
+        period_secs,
+        amount_per_period,
+        first_charge_at,
+        prepaid_lamports: amount,
+    });
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct FundSubscription<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        has_one = buyer @ EscrowError::Unauthorized,
+        seeds = [b"subscription", subscription.buyer.as_ref(), &subscription.subscription_id.to_le_bytes()],
+        bump = subscription.bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+}
+
+// Buyer prepays more periods
+pub fn fund_subscription(ctx: Context<FundSubscription>, periods: u32) -> Result<()> {
+    require!(!ctx.accounts.config.is_paused(PAUSE_FUND), EscrowError::Paused);
+    let amount = prepay(
+        &ctx.accounts.buyer,
+        &ctx.accounts.subscription,
+        &ctx.accounts.system_program,
+        periods,
+    )?;
+    let subscription = &mut ctx.accounts.subscription;
+    subscription.prepaid_lamports = subscription
+        .prepaid_lamports
+        .checked_add(amount)
+        .ok_or(EscrowError::InvalidAmount)?;
+
+    emit_cpi!(SubscriptionFunded {
+        subscription_id: subscription.subscription_id,
+        buyer: subscription.buyer,
+        amount,
+        prepaid_lamports: subscription.prepaid_lamports,
+    });
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct ChargePeriod<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"merchant", subscription.merchant.as_ref()],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, Merchant>,
+    /// CHECK: merchant's registered payout wallet
+    #[account(mut, address = merchant_account.payout_wallet)]
+    pub payout_wallet: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump = treasury.bump
+    )]
+    pub treasury: Account<'info, Treasury>,
+    #[account(
+        mut,
+        seeds = [b"subscription", subscription.buyer.as_ref(), &subscription.subscription_id.to_le_bytes()],
+        bump = subscription.bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+}
+
+// Pay the period starting at next_charge_at to the merchant minus the fee and move
+// on to the next one; returns the period start, merchant payout and fee
+fn charge_next<'info>(
+    subscription: &mut Account<'info, Subscription>,
+    payout_wallet: &UncheckedAccount<'info>,
+    treasury: &mut Account<'info, Treasury>,
+) -> Result<(i64, u64, u64)> {
+    let amount = subscription.amount_per_period;
+    require!(subscription.prepaid_lamports >= amount, EscrowError::InvalidAmount);
+
+    let fee = compute_fee(amount, subscription.fee_bps);
+    pay_from_escrow(subscription, payout_wallet, amount - fee)?;
+    pay_fee(subscription, 0, treasury, &mut None, fee)?;
+    treasury.record_release(amount, fee, false);
+
+    let period_start = subscription.next_charge_at;
+    subscription.prepaid_lamports -= amount;
+    subscription.next_charge_at = period_start.saturating_add(subscription.period_secs);
+    subscription.periods_charged += 1;
+    Ok((period_start, amount - fee, fee))
+}
+
+// Permissionless: once a period has started, pay it to the merchant minus the fee
+pub fn charge_period(ctx: Context<ChargePeriod>) -> Result<()> {
+    require!(!ctx.accounts.config.is_paused(PAUSE_RELEASE), EscrowError::Paused);
+    let now = Clock::get()?.unix_timestamp;
+    require!(now >= ctx.accounts.subscription.next_charge_at, EscrowError::NotExpired);
+    let (period_start, merchant_payout, fee) =
+        charge_next(&mut ctx.accounts.subscription, &ctx.accounts.payout_wallet, &mut ctx.accounts.treasury)?;
+
+    let subscription = &ctx.accounts.subscription;
+    emit_cpi!(SubscriptionCharged {
+        subscription_id: subscription.subscription_id,
+        buyer: subscription.buyer,
+        merchant: subscription.merchant,
+        period_start,
+        merchant_payout,
+        fee,
+        prepaid_lamports: subscription.prepaid_lamports,
+    });
+    Ok(())
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct CancelSubscription<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"merchant", subscription.merchant.as_ref()],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, Merchant>,
+    /// CHECK: merchant's registered payout wallet, paid for periods already started
+    #[account(mut, address = merchant_account.payout_wallet)]
+    pub payout_wallet: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump = treasury.bump
+    )]
+    pub treasury: Account<'info, Treasury>,
+    #[account(
+        mut,
+        has_one = buyer @ EscrowError::Unauthorized,
+        seeds = [b"subscription", subscription.buyer.as_ref(), &subscription.subscription_id.to_le_bytes()],
+        bump = subscription.bump
+    )]
+    pub subscription: Account<'info, Subscription>,
+}
+
+// Buyer stops the subscription. Periods that have already started are still owed
+// and get charged first; only future periods come back, with the rent.
+// While releases are paused the started periods cannot be charged, so the buyer gets
+// the future periods back now and the owed ones stay in the subscription for
+// charge_period once unpaused. Cancelling again after that closes it.
+pub fn cancel_subscription(ctx: Context<CancelSubscription>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let deferred = if ctx.accounts.config.is_paused(PAUSE_RELEASE) {
+        ctx.accounts.subscription.owed_lamports(now)
+    } else {
+        0
+    };
+    if deferred > 0 {
+        let subscription = &mut ctx.accounts.subscription;
+        let refunded = subscription.prepaid_lamports - deferred;
+        pay_from_escrow(&*subscription, &ctx.accounts.buyer, refunded)?;
+        subscription.prepaid_lamports = deferred;
+
+        emit_cpi!(SubscriptionCancelled {
+            subscription_id: subscription.subscription_id,
+            buyer: subscription.buyer,
+            merchant: subscription.merchant,
+            periods_charged: subscription.periods_charged,
+            refunded_lamports: refunded,
+            deferred_lamports: deferred,
+            cancelled_at: now,
+        });
+        return Ok(());
+    }
+
+    loop {
+        let subscription = &ctx.accounts.subscription;
+        if subscription.next_charge_at > now || subscription.prepaid_lamports < subscription.amount_per_period {
+            break;
+        }
+        let (period_start, merchant_payout, fee) =
+            charge_next(&mut ctx.accounts.subscription, &ctx.accounts.payout_wallet, &mut ctx.accounts.treasury)?;
+
+        let subscription = &ctx.accounts.subscription;
+        emit_cpi!(SubscriptionCharged {
+            subscription_id: subscription.subscription_id,
+            buyer: subscription.buyer,
+            merchant: subscription.merchant,
+            period_start,
+            merchant_payout,
+            fee,
+            prepaid_lamports: subscription.prepaid_lamports,
+        });
+    }
+
+    let subscription = &ctx.accounts.subscription;
+    emit_cpi!(SubscriptionCancelled {
+        subscription_id: subscription.subscription_id,
+        buyer: subscription.buyer,
+        merchant: subscription.merchant,
+        periods_charged: subscription.periods_charged,
+        refunded_lamports: subscription.prepaid_lamports,
+        deferred_lamports: 0,
+        cancelled_at: now,
+    });
+    ctx.accounts.subscription.close(ctx.accounts.buyer.to_account_info())
+}
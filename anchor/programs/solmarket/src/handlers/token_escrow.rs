@@ -0,0 +1,350 @@
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+use super::shared::{close_token_account, compute_fee, has_transfer_fee, transfer_tokens};
+use super::sol_escrow::{refund_window_allowed, release_window_allowed, EscrowError};
+use crate::state::{Config, EscrowStatus, Merchant, TokenEscrow, PAUSE_CREATE, PAUSE_FUND, PAUSE_REFUND, PAUSE_RELEASE};
+
+#[derive(Accounts)]
+#[instruction(order_id: u64, amount: u64, expires_at: i64)]
+pub struct InitializeTokenEscrow<'info> {
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+    /// CHECK: Buyer paying for the order; often equals payer
+    pub buyer: UncheckedAccount<'info>,
+    #[account(
+        seeds = [b"merchant", merchant.wallet.as_ref()],
+        bump = merchant.bump,
+        constraint = !merchant.suspended @ EscrowError::MerchantSuspended,
+        constraint = merchant.verified || !config.require_verified_merchants @ EscrowError::MerchantNotVerified
+    )]
+    pub merchant: Account<'info, Merchant>,
+    #[account(mint::token_program = token_program)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init,
+        payer = payer,
+        space = TokenEscrow::DISCRIMINATOR.len() + TokenEscrow::INIT_SPACE,
+        seeds = [b"token-escrow", buyer.key().as_ref(), &order_id.to_le_bytes()],
+        bump
+    )]
+    pub escrow: Account<'info, TokenEscrow>,
+    /// The vault is the escrow's ATA, an address anyone can create ahead of time, so an
+    /// existing one is accepted once its mint, authority and token program check out
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = mint,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+}
+
+pub fn initialize_token_escrow(
+    ctx: Context<InitializeTokenEscrow>,
+    order_id: u64,
+    amount: u64,
+    expires_at: i64,
+) -> Result<()> {
+    require!(!ctx.accounts.config.is_paused(PAUSE_CREATE), EscrowError::Paused);
+    require!(amount > 0, EscrowError::InvalidAmount);
+    let now = Clock::get()?.unix_timestamp;
+    require!(expires_at > now, EscrowError::InvalidExpiry);
+    require!(!has_transfer_fee(&ctx.accounts.mint)?, EscrowError::TransferFeeMint);
+
+    ctx.accounts.escrow.set_inner(TokenEscrow {
+        order_id,
+        buyer: ctx.accounts.buyer.key(),
+        merchant: ctx.accounts.merchant.wallet,
+        mint: ctx.accounts.mint.key(),
+        amount,
+        fee_bps: ctx.accounts.merchant.fee_bps(ctx.accounts.config.fee_bps),
+        status: EscrowStatus::Pending as u8,
+        bump: ctx.bumps.escrow,
+        created_at: now,
+        expires_at,
+    });
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct FundTokenEscrow<'info> {
+    pub token_program: Interface<'info, TokenInterface>,
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = buyer,
+        associated_token::token_program = token_program
+    )]
+    pub buyer_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        has_one = buyer @ EscrowError::Unauthorized,
+        has_one = mint,
+        seeds = [b"token-escrow", escrow.buyer.as_ref(), &escrow.order_id.to_le_bytes()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, TokenEscrow>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+}
+
+pub fn fund_token_escrow(ctx: Context<FundTokenEscrow>) -> Result<()> {
+    require!(!ctx.accounts.config.is_paused(PAUSE_FUND), EscrowError::Paused);
+    require!(ctx.accounts.escrow.status == EscrowStatus::Pending as u8, EscrowError::InvalidState);
+    // Move tokens from buyer's ATA to the vault
+    transfer_tokens(
+        &ctx.accounts.buyer_token_account,
+        &ctx.accounts.vault,
+        &ctx.accounts.escrow.amount,
+        &ctx.accounts.mint,
+        &ctx.accounts.buyer.to_account_info(),
+        &ctx.accounts.token_program,
+        None,
+    )?;
+    ctx.accounts.escrow.status = EscrowStatus::Funded as u8;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct ReleaseTokenEscrow<'info> {
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    /// Merchant before expiry, anyone during the grace window, authority at any time
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [b"merchant", escrow.merchant.as_ref()],
+        bump = merchant_account.bump
+    )]
+    pub merchant_account: Account<'info, Merchant>,
+    /// CHECK: merchant's registered payout wallet
+    #[account(mut, address = merchant_account.payout_wallet)]
+    pub payout_wallet: UncheckedAccount<'info>,
+    /// CHECK: fee recipient
+    #[account(address = config.fee_recipient)]
+    pub fee_recipient: UncheckedAccount<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = mint,
+        associated_token::authority = payout_wallet,
+        associated_token::token_program = token_program
+    )]
+    pub merchant_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = mint,
+        associated_token::authority = fee_recipient,
+        associated_token::token_program = token_program
+    )]
+    pub fee_recipient_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        close = payout_wallet,
+        has_one = mint,
+        seeds = [b"token-escrow", escrow.buyer.as_ref(), &escrow.order_id.to_le_bytes()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, TokenEscrow>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+}
+
+pub fn release_token_escrow(ctx: Context<ReleaseTokenEscrow>) -> Result<()> {
+    require!(!ctx.accounts.config.is_paused(PAUSE_RELEASE), EscrowError::Paused);
+    require!(ctx.accounts.escrow.status == EscrowStatus::Funded as u8, EscrowError::InvalidState);
+    let now = Clock::get()?.unix_timestamp;
+    let escrow = &ctx.accounts.escrow;
+    require!(
+        release_window_allowed(escrow.merchant, escrow.expires_at, &ctx.accounts.config, ctx.accounts.authority.key(), now),
+        EscrowError::Unauthorized
+    );
+
+    // Pay out whatever the vault holds, so tokens sent to it directly cannot block the close
+    let total = ctx.accounts.vault.amount;
+    let fee = compute_fee(total, ctx.accounts.escrow.fee_bps);
+    let pay_merchant = total - fee;
+
+    // Vault → merchant's payout wallet and fee_recipient, in the escrow's mint
+    let order_id_bytes = ctx.accounts.escrow.order_id.to_le_bytes();
+    let seeds: [&[u8]; 4] = [b"token-escrow", ctx.accounts.escrow.buyer.as_ref(), &order_id_bytes, &[ctx.accounts.escrow.bump]];
+    transfer_tokens(
+        &ctx.accounts.vault,
+        &ctx.accounts.merchant_token_account,
+        &pay_merchant,
+        &ctx.accounts.mint,
+        &ctx.accounts.escrow.to_account_info(),
+        &ctx.accounts.token_program,
+        Some(&seeds[..]),
+    )?;
+    if fee > 0 {
+        transfer_tokens(
+            &ctx.accounts.vault,
+            &ctx.accounts.fee_recipient_token_account,
+            &fee,
+            &ctx.accounts.mint,
+            &ctx.accounts.escrow.to_account_info(),
+            &ctx.accounts.token_program,
+            Some(&seeds[..]),
+        )?;
+    }
+    close_token_account(
+        &ctx.accounts.vault,
+        &ctx.accounts.payout_wallet.to_account_info(),
+        &ctx.accounts.escrow.to_account_info(),
+        &ctx.accounts.token_program,
+        Some(&seeds[..]),
+    )?;
+
+    ctx.accounts.escrow.status = EscrowStatus::Released as u8;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct RefundTokenEscrow<'info> {
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    /// Buyer before expiry, anyone once the grace window has closed, authority at any time
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, Config>,
+    /// CHECK: refund recipient (buyer)
+    #[account(mut, address = escrow.buyer)]
+    pub buyer_account: UncheckedAccount<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = mint,
+        associated_token::authority = buyer_account,
+        associated_token::token_program = token_program
+    )]
+    pub buyer_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        close = buyer_account,
+        has_one = mint,
+        seeds = [b"token-escrow", escrow.buyer.as_ref(), &escrow.order_id.to_le_bytes()],
+        bump = escrow.bump
+    )]
+    pub escrow: Account<'info, TokenEscrow>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = escrow,
+        associated_token::token_program = token_program
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+}
+
+pub fn refund_token_escrow(ctx: Context<RefundTokenEscrow>) -> Result<()> {
+    // With refunds paused, the buyer is the only one who can still get their tokens back
+    require!(
+        ctx.accounts.authority.key() == ctx.accounts.escrow.buyer || !ctx.accounts.config.is_paused(PAUSE_REFUND),
+        EscrowError::Paused
+    );
+    let status = ctx.accounts.escrow.status;
+    require!(status == EscrowStatus::Funded as u8 || status == EscrowStatus::Pending as u8, EscrowError::InvalidState);
+    let now = Clock::get()?.unix_timestamp;
+    let escrow = &ctx.accounts.escrow;
+    require!(
+        refund_window_allowed(escrow.buyer, escrow.expires_at, &ctx.accounts.config, ctx.accounts.authority.key(), now),
+        EscrowError::Unauthorized
+    );
+
+    return_vault_to_buyer(&ctx)?;
+    ctx.accounts.escrow.status = EscrowStatus::Refunded as u8;
+    Ok(())
+}
+
+// Expiry uses the same accounts as a refund and is permissionless: Pending escrows once
+// expires_at has passed, Funded ones once the release grace window has closed
+pub fn expire_token_escrow(ctx: Context<RefundTokenEscrow>) -> Result<()> {
+    // Normally anyone's crank, but with refunds paused only the buyer may expire their own escrow
+    require!(
+        ctx.accounts.authority.key() == ctx.accounts.escrow.buyer || !ctx.accounts.config.is_paused(PAUSE_REFUND),
+        EscrowError::Paused
+    );
+    let status = ctx.accounts.escrow.status;
+    require!(status == EscrowStatus::Funded as u8 || status == EscrowStatus::Pending as u8, EscrowError::InvalidState);
+    let now = Clock::get()?.unix_timestamp;
+    let deadline = if status == EscrowStatus::Funded as u8 {
+        ctx.accounts.escrow.expires_at.saturating_add(ctx.accounts.config.expiry_grace_secs)
+    } else {
+        ctx.accounts.escrow.expires_at
+    };
+    require!(now >= deadline, EscrowError::NotExpired);
+
+    return_vault_to_buyer(&ctx)?;
+    ctx.accounts.escrow.status = EscrowStatus::Expired as u8;
+    Ok(())
+}
+
+// Send whatever the vault holds back to the buyer, then close the vault
+fn return_vault_to_buyer(ctx: &Context<RefundTokenEscrow>) -> Result<()> {
+    let order_id_bytes = ctx.accounts.escrow.order_id.to_le_bytes();
+    let seeds: [&[u8]; 4] = [b"token-escrow", ctx.accounts.escrow.buyer.as_ref(), &order_id_bytes, &[ctx.accounts.escrow.bump]];
+    if ctx.accounts.vault.amount > 0 {
+        transfer_tokens(
+            &ctx.accounts.vault,
+            &ctx.accounts.buyer_token_account,
+            &ctx.accounts.vault.amount,
+            &ctx.accounts.mint,
+            &ctx.accounts.escrow.to_account_info(),
+            &ctx.accounts.token_program,
+            Some(&seeds[..]),
+        )?;
+    }
+    close_token_account(
+        &ctx.accounts.vault,
+        &ctx.accounts.buyer_account.to_account_info(),
+        &ctx.accounts.escrow.to_account_info(),
+        &ctx.accounts.token_program,
+        Some(&seeds[..]),
+    )
+}
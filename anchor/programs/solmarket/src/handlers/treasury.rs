@@ -0,0 +1,100 @@
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use crate::state::{Config, FeeSplit, Treasury};
+use super::shared::compute_fee;
+use super::sol_escrow::{pay_from_escrow, EscrowError};
+
+#[derive(Accounts)]
+pub struct InitializeTreasury<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = authority @ EscrowError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        init,
+        payer = authority,
+        space = Treasury::DISCRIMINATOR.len() + Treasury::INIT_SPACE,
+        seeds = [b"treasury"],
+        bump
+    )]
+    pub treasury: Account<'info, Treasury>,
+}
+
+pub fn initialize_treasury(ctx: Context<InitializeTreasury>) -> Result<()> {
+    ctx.accounts.treasury.set_inner(Treasury {
+        total_fees: 0,
+        total_withdrawn: 0,
+        escrows_released: 0,
+        volume_lamports: 0,
+        bump: ctx.bumps.treasury,
+    });
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct WithdrawFees<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = authority @ EscrowError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        mut,
+        seeds = [b"treasury"],
+        bump = treasury.bump
+    )]
+    pub treasury: Account<'info, Treasury>,
+    /// CHECK: any destination chosen by the authority; only used without fee splits
+    #[account(mut)]
+    pub destination: Option<UncheckedAccount<'info>>,
+}
+
+// Pay `amount` across the configured fee splits. Recipients are passed as the
+// leading remaining accounts, in Config order; rounding dust goes to the first one.
+fn distribute_fee<'info>(
+    from: &impl Lamports<'info>,
+    splits: &[FeeSplit],
+    recipients: &[AccountInfo<'info>],
+    amount: u64,
+) -> Result<()> {
+    require!(recipients.len() >= splits.len(), EscrowError::InvalidFeeSplits);
+    let shares: Vec<u64> = splits.iter().map(|split| compute_fee(amount, split.weight_bps)).collect();
+    let dust = amount - shares.iter().sum::<u64>();
+    for (i, (split, recipient)) in splits.iter().zip(recipients).enumerate() {
+        require_keys_eq!(recipient.key(), split.recipient, EscrowError::InvalidFeeSplits);
+        require!(recipient.is_writable, EscrowError::InvalidFeeSplits);
+        let share = if i == 0 { shares[i] + dust } else { shares[i] };
+        pay_from_escrow(from, recipient, share)?;
+    }
+    Ok(())
+}
+
+// Withdraw collected fees; the treasury always keeps its rent-exempt minimum.
+// With fee splits configured the amount is shared across their recipients, passed
+// as remaining accounts in Config order, instead of going to a destination.
+pub fn withdraw_fees<'info>(ctx: Context<'_, '_, 'info, 'info, WithdrawFees<'info>>, amount: u64) -> Result<()> {
+    require!(amount > 0, EscrowError::InvalidAmount);
+    let info = ctx.accounts.treasury.to_account_info();
+    let rent_floor = Rent::get()?.minimum_balance(info.data_len());
+    let available = info.lamports().saturating_sub(rent_floor);
+    require!(amount <= available, EscrowError::InsufficientTreasury);
+
+    let splits = ctx.accounts.config.active_fee_splits();
+    if splits.is_empty() {
+        let destination = ctx.accounts.destination.as_ref().ok_or(EscrowError::DestinationMissing)?;
+        pay_from_escrow(&ctx.accounts.treasury, destination, amount)?;
+    } else {
+        require!(ctx.accounts.destination.is_none(), EscrowError::InvalidFeeSplits);
+        distribute_fee(&ctx.accounts.treasury, splits, ctx.remaining_accounts, amount)?;
+    }
+    let treasury = &mut ctx.accounts.treasury;
+    treasury.total_withdrawn = treasury.total_withdrawn.saturating_add(amount);
+    Ok(())
+}
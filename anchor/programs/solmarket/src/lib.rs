@@ -4,9 +4,11 @@
 
 use anchor_lang::prelude::*;
 use handlers::*;
+use state::{CartLine, FeeSplit};
 
 
 pub mod error;
+pub mod events;
 pub mod handlers;
 pub mod state;
 
@@ -21,13 +23,25 @@ pub mod escrow {
     pub fn initialize_config(context: Context<InitializeConfig>) -> Result<()> {
         handlers::sol_escrow::initialize_config(context)
     }
+    #[allow(clippy::too_many_arguments)]
     pub fn initialize_escrow(
         context: Context<InitializeEscrow>,
         order_id: u64,
         amount_lamports: u64,
         expires_at: i64,
+        milestones: Vec<u64>,
+        intent_nonce: u64,
+        hash_lock: [u8; 32],
     ) -> Result<()> {
-        handlers::sol_escrow::initialize_escrow(context, order_id, amount_lamports, expires_at)
+        handlers::sol_escrow::initialize_escrow(
+            context,
+            order_id,
+            amount_lamports,
+            expires_at,
+            milestones,
+            intent_nonce,
+            hash_lock,
+        )
     }
     pub fn fund_escrow(context: Context<FundEscrow>) -> Result<()> {
         handlers::sol_escrow::fund_escrow(context)
@@ -35,12 +49,271 @@ pub mod escrow {
     pub fn release_escrow(context: Context<ReleaseEscrow>) -> Result<()> {
         handlers::sol_escrow::release_escrow(context)
     }
+    pub fn mark_shipped(context: Context<MarkShipped>, fulfilment_hash: [u8; 32]) -> Result<()> {
+        handlers::sol_escrow::mark_shipped(context, fulfilment_hash)
+    }
+    pub fn confirm_delivery(context: Context<ReleaseEscrow>) -> Result<()> {
+        handlers::sol_escrow::confirm_delivery(context)
+    }
+    pub fn release_with_secret(context: Context<ReleaseEscrow>, secret: Vec<u8>) -> Result<()> {
+        handlers::sol_escrow::release_with_secret(context, secret)
+    }
+    pub fn release_with_attestation(context: Context<ReleaseEscrow>) -> Result<()> {
+        handlers::sol_escrow::release_with_attestation(context)
+    }
+    pub fn release_milestone(context: Context<ReleaseMilestone>) -> Result<()> {
+        handlers::sol_escrow::release_milestone(context)
+    }
     pub fn refund_escrow(context: Context<RefundEscrow>) -> Result<()> {
         handlers::sol_escrow::refund_escrow(context)
     }
+    pub fn partial_refund(context: Context<PartialRefund>, amount: u64) -> Result<()> {
+        handlers::sol_escrow::partial_refund(context, amount)
+    }
+    pub fn decline_order(context: Context<DeclineOrder>, reason_code: u8) -> Result<()> {
+        handlers::sol_escrow::decline_order(context, reason_code)
+    }
+    pub fn mutual_cancel(context: Context<MutualCancel>) -> Result<()> {
+        handlers::sol_escrow::mutual_cancel(context)
+    }
+    pub fn extend_expiry(context: Context<ExtendExpiry>, new_expires_at: i64) -> Result<()> {
+        handlers::sol_escrow::extend_expiry(context, new_expires_at)
+    }
     pub fn expire_escrow(context: Context<ExpireEscrow>) -> Result<()> {
         handlers::sol_escrow::expire_escrow(context)
     }
+    pub fn crank_expire(context: Context<CrankExpire>) -> Result<()> {
+        handlers::sol_escrow::crank_expire(context)
+    }
+    pub fn close_order_intent(context: Context<CloseOrderIntent>) -> Result<()> {
+        handlers::order_intent::close_order_intent(context)
+    }
+
+    // Batch settlement
+    pub fn batch_release<'info>(context: Context<'_, '_, 'info, 'info, BatchRelease<'info>>) -> Result<()> {
+        handlers::batch::batch_release(context)
+    }
+    pub fn batch_refund<'info>(context: Context<'_, '_, 'info, 'info, BatchRefund<'info>>) -> Result<()> {
+        handlers::batch::batch_refund(context)
+    }
+
+    // Multi-merchant cart escrow API
+    pub fn initialize_cart<'info>(
+        context: Context<'_, '_, 'info, 'info, InitializeCart<'info>>,
+        cart_id: u64,
+        expires_at: i64,
+        lines: Vec<CartLine>,
+    ) -> Result<()> {
+        handlers::cart_escrow::initialize_cart(context, cart_id, expires_at, lines)
+    }
+    pub fn fund_cart(context: Context<FundCart>) -> Result<()> {
+        handlers::cart_escrow::fund_cart(context)
+    }
+    pub fn release_cart_item(context: Context<ReleaseCartItem>, index: u8) -> Result<()> {
+        handlers::cart_escrow::release_cart_item(context, index)
+    }
+    pub fn refund_cart_item(context: Context<RefundCartItem>, index: u8) -> Result<()> {
+        handlers::cart_escrow::refund_cart_item(context, index)
+    }
+
+    // Subscriptions
+    pub fn create_subscription(
+        context: Context<CreateSubscription>,
+        subscription_id: u64,
+        period_secs: i64,
+        amount_per_period: u64,
+        first_charge_at: i64,
+        periods: u32,
+    ) -> Result<()> {
+        handlers::subscription::create_subscription(
+            context,
+            subscription_id,
+            period_secs,
+            amount_per_period,
+            first_charge_at,
+            periods,
+        )
+    }
+    pub fn fund_subscription(context: Context<FundSubscription>, periods: u32) -> Result<()> {
+        handlers::subscription::fund_subscription(context, periods)
+    }
+    pub fn charge_period(context: Context<ChargePeriod>) -> Result<()> {
+        handlers::subscription::charge_period(context)
+    }
+    pub fn cancel_subscription(context: Context<CancelSubscription>) -> Result<()> {
+        handlers::subscription::cancel_subscription(context)
+    }
+
+    // Disputes
+    pub fn raise_dispute(context: Context<RaiseDispute>) -> Result<()> {
+        handlers::dispute::raise_dispute(context)
+    }
+    pub fn resolve_dispute(context: Context<ResolveDispute>, buyer_bps: u16) -> Result<()> {
+        handlers::dispute::resolve_dispute(context, buyer_bps)
+    }
+
+    // SPL / Token-2022 escrow API
+    pub fn initialize_token_escrow(
+        context: Context<InitializeTokenEscrow>,
+        order_id: u64,
+        amount: u64,
+        expires_at: i64,
+    ) -> Result<()> {
+        handlers::token_escrow::initialize_token_escrow(context, order_id, amount, expires_at)
+    }
+    pub fn fund_token_escrow(context: Context<FundTokenEscrow>) -> Result<()> {
+        handlers::token_escrow::fund_token_escrow(context)
+    }
+    pub fn release_token_escrow(context: Context<ReleaseTokenEscrow>) -> Result<()> {
+        handlers::token_escrow::release_token_escrow(context)
+    }
+    pub fn refund_token_escrow(context: Context<RefundTokenEscrow>) -> Result<()> {
+        handlers::token_escrow::refund_token_escrow(context)
+    }
+    pub fn expire_token_escrow(context: Context<RefundTokenEscrow>) -> Result<()> {
+        handlers::token_escrow::expire_token_escrow(context)
+    }
+
+    // Merchant registry
+    pub fn register_merchant(context: Context<RegisterMerchant>) -> Result<()> {
+        handlers::merchant::register_merchant(context)
+    }
+    pub fn set_payout_wallet(context: Context<SetPayoutWallet>) -> Result<()> {
+        handlers::merchant::set_payout_wallet(context)
+    }
+    pub fn set_merchant_verified(context: Context<UpdateMerchant>, verified: bool) -> Result<()> {
+        handlers::merchant::set_merchant_verified(context, verified)
+    }
+    pub fn set_merchant_suspended(context: Context<UpdateMerchant>, suspended: bool) -> Result<()> {
+        handlers::merchant::set_merchant_suspended(context, suspended)
+    }
+    pub fn set_merchant_fee(context: Context<UpdateMerchant>, fee_bps_override: Option<u16>) -> Result<()> {
+        handlers::merchant::set_merchant_fee(context, fee_bps_override)
+    }
+
+    // Buyer exposure
+    pub fn initialize_buyer_stats(context: Context<InitializeBuyerStats>) -> Result<()> {
+        handlers::buyer_stats::initialize_buyer_stats(context)
+    }
+
+    // Merchant bonds
+    pub fn post_bond(context: Context<PostBond>, lamports: u64) -> Result<()> {
+        handlers::bond::post_bond(context, lamports)
+    }
+    pub fn post_bond_tokens(context: Context<PostBondTokens>, amount: u64) -> Result<()> {
+        handlers::bond::post_bond_tokens(context, amount)
+    }
+    pub fn request_bond_withdrawal(context: Context<RequestBondWithdrawal>, lamports: u64, tokens: u64) -> Result<()> {
+        handlers::bond::request_bond_withdrawal(context, lamports, tokens)
+    }
+    pub fn withdraw_bond(context: Context<WithdrawBond>) -> Result<()> {
+        handlers::bond::withdraw_bond(context)
+    }
+    pub fn withdraw_bond_tokens(context: Context<WithdrawBondTokens>) -> Result<()> {
+        handlers::bond::withdraw_bond_tokens(context)
+    }
+    pub fn slash_bond(context: Context<SlashBond>, lamports: u64) -> Result<()> {
+        handlers::bond::slash_bond(context, lamports)
+    }
+    pub fn slash_bond_tokens(context: Context<SlashBondTokens>, amount: u64) -> Result<()> {
+        handlers::bond::slash_bond_tokens(context, amount)
+    }
+
+    // Fee treasury
+    pub fn initialize_treasury(context: Context<InitializeTreasury>) -> Result<()> {
+        handlers::treasury::initialize_treasury(context)
+    }
+    pub fn withdraw_fees<'info>(context: Context<'_, '_, 'info, 'info, WithdrawFees<'info>>, amount: u64) -> Result<()> {
+        handlers::treasury::withdraw_fees(context, amount)
+    }
+
+    // Referrals
+    pub fn register_referrer(context: Context<RegisterReferrer>, referral_bps: u16) -> Result<()> {
+        handlers::referrer::register_referrer(context, referral_bps)
+    }
+    pub fn set_referral_bps(context: Context<UpdateReferrer>, referral_bps: u16) -> Result<()> {
+        handlers::referrer::set_referral_bps(context, referral_bps)
+    }
+    pub fn claim_referral_fees(context: Context<ClaimReferralFees>) -> Result<()> {
+        handlers::referrer::claim_referral_fees(context)
+    }
+
+    // Layout migrations
+    pub fn migrate_config(context: Context<MigrateConfig>) -> Result<()> {
+        handlers::migrate::migrate_config(context)
+    }
+    pub fn migrate_escrow(context: Context<MigrateEscrow>) -> Result<()> {
+        handlers::migrate::migrate_escrow(context)
+    }
+
+    // Config administration
+    pub fn update_fee(context: Context<UpdateConfig>, fee_bps: u16) -> Result<()> {
+        handlers::admin::update_fee(context, fee_bps)
+    }
+    pub fn set_fee_recipient(context: Context<SetFeeRecipient>) -> Result<()> {
+        handlers::admin::set_fee_recipient(context)
+    }
+    pub fn set_arbiter(context: Context<UpdateConfig>, arbiter: Pubkey) -> Result<()> {
+        handlers::admin::set_arbiter(context, arbiter)
+    }
+    pub fn set_expiry_grace(context: Context<UpdateConfig>, expiry_grace_secs: i64) -> Result<()> {
+        handlers::admin::set_expiry_grace(context, expiry_grace_secs)
+    }
+    pub fn set_review_period(context: Context<UpdateConfig>, review_period_secs: i64) -> Result<()> {
+        handlers::admin::set_review_period(context, review_period_secs)
+    }
+    pub fn set_max_escrow_duration(context: Context<UpdateConfig>, max_escrow_duration_secs: i64) -> Result<()> {
+        handlers::admin::set_max_escrow_duration(context, max_escrow_duration_secs)
+    }
+    pub fn set_order_signer(context: Context<UpdateConfig>, order_signer: Pubkey) -> Result<()> {
+        handlers::admin::set_order_signer(context, order_signer)
+    }
+    pub fn set_max_referral_bps(context: Context<UpdateConfig>, max_referral_bps: u16) -> Result<()> {
+        handlers::admin::set_max_referral_bps(context, max_referral_bps)
+    }
+    pub fn set_fee_splits(context: Context<UpdateConfig>, splits: Vec<FeeSplit>) -> Result<()> {
+        handlers::admin::set_fee_splits(context, splits)
+    }
+    pub fn set_bond_mint(context: Context<UpdateConfig>, bond_mint: Pubkey) -> Result<()> {
+        handlers::admin::set_bond_mint(context, bond_mint)
+    }
+    pub fn set_bond_token_rate(context: Context<UpdateConfig>, bond_token_rate: u64) -> Result<()> {
+        handlers::admin::set_bond_token_rate(context, bond_token_rate)
+    }
+    pub fn set_min_bond_bps(context: Context<UpdateConfig>, min_bond_bps: u16) -> Result<()> {
+        handlers::admin::set_min_bond_bps(context, min_bond_bps)
+    }
+    pub fn set_bond_withdraw_delay(context: Context<UpdateConfig>, bond_withdraw_delay_secs: i64) -> Result<()> {
+        handlers::admin::set_bond_withdraw_delay(context, bond_withdraw_delay_secs)
+    }
+    pub fn set_attester(context: Context<UpdateConfig>, attester: Pubkey) -> Result<()> {
+        handlers::admin::set_attester(context, attester)
+    }
+    pub fn set_crank_bounty(context: Context<UpdateConfig>, crank_bounty_lamports: u64) -> Result<()> {
+        handlers::admin::set_crank_bounty(context, crank_bounty_lamports)
+    }
+    pub fn set_order_limits(context: Context<UpdateConfig>, min_order_lamports: u64, max_order_lamports: u64) -> Result<()> {
+        handlers::admin::set_order_limits(context, min_order_lamports, max_order_lamports)
+    }
+    pub fn set_buyer_caps(
+        context: Context<UpdateConfig>,
+        max_open_escrows_per_buyer: u32,
+        max_buyer_exposure_lamports: u64,
+    ) -> Result<()> {
+        handlers::admin::set_buyer_caps(context, max_open_escrows_per_buyer, max_buyer_exposure_lamports)
+    }
+    pub fn set_require_verified_merchants(context: Context<UpdateConfig>, require_verified_merchants: bool) -> Result<()> {
+        handlers::admin::set_require_verified_merchants(context, require_verified_merchants)
+    }
+    pub fn set_paused(context: Context<UpdateConfig>, paused: u8) -> Result<()> {
+        handlers::admin::set_paused(context, paused)
+    }
+    pub fn propose_authority(context: Context<UpdateConfig>, new_authority: Pubkey) -> Result<()> {
+        handlers::admin::propose_authority(context, new_authority)
+    }
+    pub fn accept_authority(context: Context<AcceptAuthority>) -> Result<()> {
+        handlers::admin::accept_authority(context)
+    }
 }
 
 // Tests removed in this build to simplify IDL generation
\ No newline at end of file
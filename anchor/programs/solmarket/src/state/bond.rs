@@ -0,0 +1,31 @@
+use anchor_lang::prelude::*;
+
+// Collateral a merchant posts against buyer losses, in lamports and/or the
+// Config.bond_mint token (held in the bond's ATA for `mint`). Lamports are tracked
+// apart from the account's rent.
+#[account]
+#[derive(InitSpace)]
+pub struct MerchantBond {
+    pub merchant: Pubkey,             // merchant wallet
+    pub lamports: u64,
+    pub tokens: u64,
+    pub mint: Pubkey,                 // mint the tokens were posted in; stays until they are all withdrawn or slashed
+    pub token_decimals: u8,           // of mint, recorded when tokens are first posted
+    pub pending_lamports: u64,        // requested for withdrawal, no longer counts as collateral
+    pub pending_tokens: u64,
+    pub withdraw_requested_at: i64,   // 0 when no withdrawal is pending
+    pub total_slashed_lamports: u64,
+    pub total_slashed_tokens: u64,
+    pub bump: u8,
+}
+
+impl MerchantBond {
+    // Collateral in lamports, valuing tokens at `token_rate` lamports per whole token.
+    // Tokens posted in a mint other than the current `bond_mint` count for nothing.
+    pub fn value_lamports(&self, bond_mint: &Pubkey, token_rate: u64) -> u64 {
+        let tokens = if self.mint == *bond_mint { self.tokens.saturating_sub(self.pending_tokens) } else { 0 } as u128;
+        let token_value = tokens * token_rate as u128 / 10u128.pow(self.token_decimals as u32);
+        let value = self.lamports.saturating_sub(self.pending_lamports) as u128 + token_value;
+        value.min(u64::MAX as u128) as u64
+    }
+}
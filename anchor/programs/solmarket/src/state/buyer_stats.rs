@@ -0,0 +1,24 @@
+use anchor_lang::prelude::*;
+
+// Per-buyer exposure, counted while escrows tracked in it are open; backs the
+// Config buyer caps
+#[account]
+#[derive(InitSpace)]
+pub struct BuyerStats {
+    pub buyer: Pubkey,
+    pub open_escrows: u32,
+    pub lamports_in_flight: u64, // order amounts of the open escrows
+    pub bump: u8,
+}
+
+impl BuyerStats {
+    pub fn record_open(&mut self, amount: u64) {
+        self.open_escrows = self.open_escrows.saturating_add(1);
+        self.lamports_in_flight = self.lamports_in_flight.saturating_add(amount);
+    }
+
+    pub fn record_settled(&mut self, amount: u64) {
+        self.open_escrows = self.open_escrows.saturating_sub(1);
+        self.lamports_in_flight = self.lamports_in_flight.saturating_sub(amount);
+    }
+}
@@ -0,0 +1,34 @@
+use anchor_lang::prelude::*;
+
+pub const MAX_CART_ITEMS: usize = 8;
+
+// One merchant's share of a cart; status values reuse EscrowStatus
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct CartItem {
+    pub merchant: Pubkey,
+    pub amount_lamports: u64,
+    pub fee_bps: u16, // snapshot of the merchant's fee at creation
+    pub status: u8,
+}
+
+// Escrow for a checkout spanning several merchants, funded in one transfer
+#[account]
+#[derive(InitSpace)]
+pub struct CartEscrow {
+    pub cart_id: u64,
+    pub buyer: Pubkey,
+    pub payer: Pubkey,
+    pub status: u8, // Pending until funded, then Funded until every line settles
+    pub bump: u8,
+    pub created_at: i64,
+    pub expires_at: i64,
+    #[max_len(MAX_CART_ITEMS)]
+    pub items: Vec<CartItem>,
+}
+
+// Instruction input for one cart line
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct CartLine {
+    pub merchant: Pubkey,
+    pub amount_lamports: u64,
+}
@@ -1,15 +1,68 @@
 use anchor_lang::prelude::*;
 
+// Bits of Config.paused
+pub const PAUSE_CREATE: u8 = 1 << 0;
+pub const PAUSE_FUND: u8 = 1 << 1;
+pub const PAUSE_RELEASE: u8 = 1 << 2;
+pub const PAUSE_REFUND: u8 = 1 << 3;
+// Withdraw-only: nothing new comes in or goes to merchants, buyers can still get refunds
+pub const PAUSE_WITHDRAW_ONLY: u8 = PAUSE_CREATE | PAUSE_FUND | PAUSE_RELEASE;
+
+pub const CONFIG_VERSION: u8 = 10;
+pub const MAX_FEE_SPLITS: usize = 4;
+
+// One recipient of treasury withdrawals; weights across Config.fee_splits sum to 10_000.
+// Settlements pay the whole platform fee into the treasury; it is split only when withdraw_fees pays it out.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct FeeSplit {
+    pub recipient: Pubkey,
+    pub weight_bps: u16,
+}
+
+// Same layout rules as SolEscrow: fixed-size fields, only ever appended.
+// Version 0 is the original layout, ending at bump.
 #[account]
 #[derive(InitSpace)]
 pub struct Config {
     pub authority: Pubkey,
     pub fee_bps: u16,         // 200 for 2%
-    pub fee_recipient: Pubkey,
+    pub fee_recipient: Pubkey, // token escrow fees; SOL fees go to the treasury PDA
     pub bump: u8,
+    pub pending_authority: Pubkey,         // set by propose_authority, default when none
+    pub arbiter: Pubkey,                   // resolves disputed escrows
+    pub expiry_grace_secs: i64,            // after expires_at, release stays the default for this long
+    pub review_period_secs: i64,           // buyer's window after shipment before release is permissionless
+    pub paused: u8,                        // PAUSE_* bits
+    pub version: u8,                       // CONFIG_VERSION once created or migrated
+    pub max_escrow_duration_secs: i64,     // v2: latest expires_at allowed, counted from created_at
+    pub order_signer: Pubkey,              // v3: signs order intents; default when intents are not required
+    pub max_referral_bps: u16,             // v4: cap on a referrer's share of the fee; 0 disables referrals
+    pub fee_splits: [FeeSplit; MAX_FEE_SPLITS], // v5: how withdraw_fees shares out the treasury, in order
+    pub fee_split_count: u8,               // v5: 0 withdraws to a single destination
+    pub bond_mint: Pubkey,                 // v6: token merchants may bond in (MKN), default for lamports only
+    pub bond_token_rate: u64,              // v6: lamports of bond value per whole bond_mint token
+    pub min_bond_bps: u16,                 // v6: bond required at escrow creation, in bps of the order; 0 disables
+    pub bond_withdraw_delay_secs: i64,     // v6: timelock between requesting and withdrawing a bond
+    pub attester: Pubkey,                  // v7: signs delivery attestations; default disables attested release
+    pub crank_bounty_lamports: u64,        // v8: paid out of an escrow's rent to whoever cranks its expiry
+    pub min_order_lamports: u64,           // v9: smallest SOL escrow amount
+    pub max_order_lamports: u64,           // v9: largest SOL escrow amount, 0 for no limit
+    pub max_open_escrows_per_buyer: u32,   // v9: 0 for no limit
+    pub max_buyer_exposure_lamports: u64,  // v9: cap on a buyer's open order amounts, 0 for no limit
+    pub require_verified_merchants: bool,  // v10: only merchants with Merchant.verified take new orders
 }
 
+impl Config {
+    pub fn is_paused(&self, flag: u8) -> bool {
+        self.paused & flag != 0
+    }
 
+    // With either buyer cap set, escrows must be tracked in BuyerStats as they are funded
+    pub fn has_buyer_caps(&self) -> bool {
+        self.max_open_escrows_per_buyer > 0 || self.max_buyer_exposure_lamports > 0
+    }
 
-
-
+    pub fn active_fee_splits(&self) -> &[FeeSplit] {
+        &self.fee_splits[..self.fee_split_count as usize]
+    }
+}
@@ -0,0 +1,16 @@
+use anchor_lang::prelude::*;
+
+// Record of a dispute on a SolEscrow; outlives the escrow so the decision stays on-chain.
+// Keyed by the escrow's address and created_at, since the escrow PDA is reused once closed.
+#[account]
+#[derive(InitSpace)]
+pub struct Dispute {
+    pub escrow: Pubkey,
+    pub order_id: u64,
+    pub raised_by: Pubkey,
+    pub raised_at: i64,
+    pub arbiter: Pubkey,  // set on resolution; stays default when the parties cancel
+    pub buyer_bps: u16,   // buyer's share of the escrowed amount
+    pub resolved_at: i64, // 0 while open
+    pub bump: u8,
+}
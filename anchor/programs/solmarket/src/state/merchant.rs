@@ -0,0 +1,20 @@
+use anchor_lang::prelude::*;
+
+// Registry entry for a merchant wallet; escrows can only be opened to registered merchants
+#[account]
+#[derive(InitSpace)]
+pub struct Merchant {
+    pub wallet: Pubkey,                // signing key, used in escrow.merchant
+    pub payout_wallet: Pubkey,         // receives releases
+    pub verified: bool,                // set by the platform authority; required for new orders under Config.require_verified_merchants
+    pub suspended: bool,               // suspended merchants cannot receive new escrows
+    pub fee_bps_override: Option<u16>, // replaces Config.fee_bps when set
+    pub registered_at: i64,
+    pub bump: u8,
+}
+
+impl Merchant {
+    pub fn fee_bps(&self, default_fee_bps: u16) -> u16 {
+        self.fee_bps_override.unwrap_or(default_fee_bps)
+    }
+}
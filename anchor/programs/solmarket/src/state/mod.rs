@@ -1,7 +1,27 @@
 pub mod offer;
 pub mod sol_escrow;
+pub mod token_escrow;
+pub mod cart_escrow;
 pub mod config;
+pub mod merchant;
+pub mod treasury;
+pub mod dispute;
+pub mod bond;
+pub mod subscription;
+pub mod buyer_stats;
+pub mod order_intent;
+pub mod referrer;
 
 pub use offer::*;
 pub use sol_escrow::*;
-pub use config::*;
\ No newline at end of file
+pub use token_escrow::*;
+pub use cart_escrow::*;
+pub use config::*;
+pub use merchant::*;
+pub use treasury::*;
+pub use dispute::*;
+pub use bond::*;
+pub use subscription::*;
+pub use buyer_stats::*;
+pub use order_intent::*;
+pub use referrer::*;
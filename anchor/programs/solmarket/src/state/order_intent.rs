@@ -0,0 +1,14 @@
+use anchor_lang::prelude::*;
+
+// Marks a signed order intent as used so it cannot open a second escrow. Once the
+// intent's expires_at has passed initialize_escrow rejects it anyway, and the
+// record can be closed.
+#[account]
+#[derive(InitSpace)]
+pub struct OrderIntent {
+    pub buyer: Pubkey,
+    pub nonce: u64,
+    pub payer: Pubkey, // paid the rent; receives it back on close
+    pub expires_at: i64,
+    pub bump: u8,
+}
@@ -0,0 +1,20 @@
+use anchor_lang::prelude::*;
+
+// Affiliate registered by the platform authority. Referral fees accrue to this
+// account as lamports at settlement; the wallet claims them with claim_referral_fees.
+#[account]
+#[derive(InitSpace)]
+pub struct Referrer {
+    pub wallet: Pubkey,     // claims accrued fees
+    pub referral_bps: u16,  // share of the fee on escrows it refers, snapshotted at creation
+    pub total_earned: u64,  // cumulative referral fees accrued
+    pub total_claimed: u64, // cumulative referral fees claimed
+    pub registered_at: i64,
+    pub bump: u8,
+}
+
+impl Referrer {
+    pub fn record_referral(&mut self, fee: u64) {
+        self.total_earned = self.total_earned.saturating_add(fee);
+    }
+}
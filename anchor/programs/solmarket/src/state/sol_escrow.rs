@@ -1,5 +1,8 @@
 use anchor_lang::prelude::*;
 
+pub const MAX_MILESTONES: usize = 4;
+pub const SOL_ESCROW_VERSION: u8 = 5;
+
 #[repr(u8)]
 pub enum EscrowStatus {
     Pending = 0,
@@ -7,8 +10,16 @@ pub enum EscrowStatus {
     Released = 2,
     Refunded = 3,
     Expired = 4,
+    Disputed = 5,
+    Resolved = 6,
+    Shipped = 7,
+    Declined = 8,
+    Cancelled = 9,
 }
 
+// Layout rules: every field is fixed-size and new fields are only ever appended,
+// so an older account upgrades by zero-extending it (see handlers::migrate).
+// Version 0 is the original layout, ending at expires_at.
 #[account]
 #[derive(InitSpace)]
 pub struct SolEscrow {
@@ -21,6 +32,40 @@ pub struct SolEscrow {
     pub bump: u8,
     pub created_at: i64,
     pub expires_at: i64,
+    pub payer: Pubkey, // paid the rent; receives it back when a Pending escrow expires
+    pub milestones: [u64; MAX_MILESTONES], // payment schedule, sums to amount_lamports
+    pub milestone_count: u8,               // 0 for a single full release
+    pub milestones_paid: u8,
+    pub released_lamports: u64,            // paid out so far through milestones
+    pub refunded_lamports: u64,            // returned to the buyer through partial refunds
+    pub fulfilment_hash: [u8; 32],         // set by mark_shipped
+    pub shipped_at: i64,                   // 0 until shipped
+    pub version: u8,                       // SOL_ESCROW_VERSION once created or migrated
+    pub intent_nonce: u64,                 // v2: nonce of the signed order intent, 0 without one
+    pub referrer: Pubkey,                  // v3: wallet of the registered Referrer credited with part of the fee, default when none
+    pub referral_bps: u16,                 // v3: referrer's share of the fee
+    pub hash_lock: [u8; 32],               // v4: sha256 the merchant's secret must match, zero when not hash-locked
+    pub tracked_in_buyer_stats: bool,      // v5: counted in the buyer's BuyerStats until settled
+}
+
+impl SolEscrow {
+    // Lamports still owed to someone, excluding rent
+    pub fn remaining_lamports(&self) -> u64 {
+        self.amount_lamports - self.released_lamports - self.refunded_lamports
+    }
+
+    pub fn is_hash_locked(&self) -> bool {
+        self.hash_lock != [0u8; 32]
+    }
+
+    // What a full refund pays the buyer; a Pending escrow only holds rent
+    pub fn refundable_lamports(&self) -> u64 {
+        if self.status == EscrowStatus::Pending as u8 {
+            0
+        } else {
+            self.remaining_lamports()
+        }
+    }
 }
 
 
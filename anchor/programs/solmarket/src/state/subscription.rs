@@ -0,0 +1,31 @@
+use anchor_lang::prelude::*;
+
+// Prepaid recurring payment: the buyer funds whole periods up front and each one
+// is paid to the merchant by charge_period once it starts
+#[account]
+#[derive(InitSpace)]
+pub struct Subscription {
+    pub subscription_id: u64,
+    pub buyer: Pubkey,
+    pub merchant: Pubkey,
+    pub period_secs: i64,
+    pub amount_per_period: u64,
+    pub fee_bps: u16,           // snapshot of fee at creation
+    pub next_charge_at: i64,    // start of the next period to be paid
+    pub prepaid_lamports: u64,  // funded and not yet charged, excluding rent
+    pub periods_charged: u32,
+    pub created_at: i64,
+    pub bump: u8,
+}
+
+impl Subscription {
+    // Prepaid lamports covering periods that have started but are not charged yet
+    pub fn owed_lamports(&self, now: i64) -> u64 {
+        if now < self.next_charge_at {
+            return 0;
+        }
+        let started = ((now - self.next_charge_at) / self.period_secs) as u64 + 1;
+        let covered = self.prepaid_lamports / self.amount_per_period;
+        started.min(covered) * self.amount_per_period
+    }
+}
@@ -0,0 +1,17 @@
+use anchor_lang::prelude::*;
+
+// SPL / Token-2022 counterpart of SolEscrow; status values reuse EscrowStatus
+#[account]
+#[derive(InitSpace)]
+pub struct TokenEscrow {
+    pub order_id: u64,
+    pub buyer: Pubkey,
+    pub merchant: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub fee_bps: u16, // snapshot of fee at creation
+    pub status: u8,
+    pub bump: u8,
+    pub created_at: i64,
+    pub expires_at: i64,
+}
@@ -0,0 +1,22 @@
+use anchor_lang::prelude::*;
+
+// Program-owned account that collects platform fees in lamports
+#[account]
+#[derive(InitSpace)]
+pub struct Treasury {
+    pub total_fees: u64,       // cumulative platform fees, net of referral shares
+    pub total_withdrawn: u64,  // cumulative fees withdrawn
+    pub escrows_released: u64, // escrows (or cart lines) fully released
+    pub volume_lamports: u64,  // cumulative amount released, before fees
+    pub bump: u8,
+}
+
+impl Treasury {
+    pub fn record_release(&mut self, volume: u64, fee: u64, completed: bool) {
+        self.total_fees = self.total_fees.saturating_add(fee);
+        self.volume_lamports = self.volume_lamports.saturating_add(volume);
+        if completed {
+            self.escrows_released = self.escrows_released.saturating_add(1);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::{instruction::Instruction, native_token::LAMPORTS_PER_SOL, system_program};
+use solmarket::handlers::EscrowError;
+use solmarket::state::Config;
+use solmarket_test_runtime::{instruction, pda, program_error, TestBank};
+
+struct Setup {
+    bank: TestBank,
+    authority: Pubkey,
+    stranger: Pubkey,
+    config: Pubkey,
+}
+
+fn setup() -> Setup {
+    let mut bank = TestBank::new();
+    let authority = Pubkey::new_unique();
+    let stranger = Pubkey::new_unique();
+    let config = pda(&[b"config"]);
+    bank.airdrop(&authority, LAMPORTS_PER_SOL);
+    bank.airdrop(&stranger, LAMPORTS_PER_SOL);
+    let ix = instruction(
+        solmarket::accounts::InitializeConfig {
+            authority,
+            system_program: system_program::ID,
+            config,
+            fee_recipient: authority,
+        },
+        solmarket::instruction::InitializeConfig {},
+    );
+    bank.process(&[ix], &[&authority]).unwrap();
+    Setup { bank, authority, stranger, config }
+}
+
+fn update_fee(s: &Setup, signer: Pubkey, fee_bps: u16) -> Instruction {
+    instruction(
+        solmarket::accounts::UpdateConfig { authority: signer, config: s.config },
+        solmarket::instruction::UpdateFee { fee_bps },
+    )
+}
+
+fn set_fee_recipient(s: &Setup, signer: Pubkey, fee_recipient: Pubkey) -> Instruction {
+    instruction(
+        solmarket::accounts::SetFeeRecipient { authority: signer, config: s.config, fee_recipient },
+        solmarket::instruction::SetFeeRecipient {},
+    )
+}
+
+fn propose_authority(s: &Setup, signer: Pubkey, new_authority: Pubkey) -> Instruction {
+    instruction(
+        solmarket::accounts::UpdateConfig { authority: signer, config: s.config },
+        solmarket::instruction::ProposeAuthority { new_authority },
+    )
+}
+
+fn accept_authority(s: &Setup, signer: Pubkey) -> Instruction {
+    instruction(
+        solmarket::accounts::AcceptAuthority { new_authority: signer, config: s.config },
+        solmarket::instruction::AcceptAuthority {},
+    )
+}
+
+#[test]
+fn update_fee_requires_the_authority() {
+    let mut s = setup();
+    let ix = update_fee(&s, s.stranger, 500);
+    let err = s.bank.process(&[ix], &[&s.stranger]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::Unauthorized));
+    assert_eq!(s.bank.anchor_account::<Config>(&s.config).fee_bps, 200);
+
+    let ix = update_fee(&s, s.authority, 500);
+    s.bank.process(&[ix], &[&s.authority]).unwrap();
+    assert_eq!(s.bank.anchor_account::<Config>(&s.config).fee_bps, 500);
+}
+
+#[test]
+fn update_fee_requires_a_signature() {
+    let mut s = setup();
+    let ix = update_fee(&s, s.authority, 500);
+    let err = s.bank.process(&[ix], &[]).unwrap_err();
+    assert_eq!(err, anchor_lang::solana_program::program_error::ProgramError::MissingRequiredSignature);
+}
+
+#[test]
+fn set_fee_recipient_requires_the_authority() {
+    let mut s = setup();
+    let recipient = Pubkey::new_unique();
+    let ix = set_fee_recipient(&s, s.stranger, recipient);
+    let err = s.bank.process(&[ix], &[&s.stranger]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::Unauthorized));
+    assert_eq!(s.bank.anchor_account::<Config>(&s.config).fee_recipient, s.authority);
+
+    let ix = set_fee_recipient(&s, s.authority, recipient);
+    s.bank.process(&[ix], &[&s.authority]).unwrap();
+    assert_eq!(s.bank.anchor_account::<Config>(&s.config).fee_recipient, recipient);
+}
+
+#[test]
+fn propose_authority_requires_the_authority() {
+    let mut s = setup();
+    let ix = propose_authority(&s, s.stranger, s.stranger);
+    let err = s.bank.process(&[ix], &[&s.stranger]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::Unauthorized));
+    assert_eq!(s.bank.anchor_account::<Config>(&s.config).pending_authority, Pubkey::default());
+}
+
+#[test]
+fn accept_authority_requires_the_proposed_key() {
+    let mut s = setup();
+    let successor = Pubkey::new_unique();
+    s.bank.airdrop(&successor, LAMPORTS_PER_SOL);
+
+    // Nothing to accept before a proposal
+    let ix = accept_authority(&s, successor);
+    let err = s.bank.process(&[ix], &[&successor]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::NoPendingAuthority));
+
+    let ix = propose_authority(&s, s.authority, successor);
+    s.bank.process(&[ix], &[&s.authority]).unwrap();
+
+    // Neither a stranger nor the current authority can take the proposal
+    for signer in [s.stranger, s.authority] {
+        let ix = accept_authority(&s, signer);
+        let err = s.bank.process(&[ix], &[&signer]).unwrap_err();
+        assert_eq!(err, program_error(EscrowError::Unauthorized));
+    }
+
+    let ix = accept_authority(&s, successor);
+    s.bank.process(&[ix], &[&successor]).unwrap();
+    let config = s.bank.anchor_account::<Config>(&s.config);
+    assert_eq!(config.authority, successor);
+    assert_eq!(config.pending_authority, Pubkey::default());
+
+    // The previous authority has lost access
+    let ix = update_fee(&s, s.authority, 500);
+    let err = s.bank.process(&[ix], &[&s.authority]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::Unauthorized));
+}
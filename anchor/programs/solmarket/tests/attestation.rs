@@ -0,0 +1,106 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::{instruction::Instruction, native_token::LAMPORTS_PER_SOL, sysvar};
+use solana_sdk::signature::{Keypair, Signer};
+use solmarket::handlers::{delivery_attestation_message, EscrowError};
+use solmarket::state::{Config, SolEscrow};
+use solmarket_test_runtime::market::{Market, DAY};
+use solmarket_test_runtime::{ed25519_verify, instruction, program_error};
+
+const AMOUNT: u64 = LAMPORTS_PER_SOL;
+
+struct Setup {
+    m: Market,
+    attester: Keypair,
+    cranker: Pubkey,
+}
+
+fn setup() -> Setup {
+    let mut m = Market::new();
+    let attester = Keypair::new();
+    let ix = instruction(
+        solmarket::accounts::UpdateConfig { authority: m.authority, config: m.config },
+        solmarket::instruction::SetAttester { attester: attester.pubkey() },
+    );
+    m.bank.process(&[ix], &[&m.authority]).unwrap();
+    let cranker = Pubkey::new_unique();
+    m.bank.airdrop(&cranker, LAMPORTS_PER_SOL);
+    Setup { m, attester, cranker }
+}
+
+// What the attester signs for the escrow currently at `order_id`'s address
+fn attestation(m: &Market, order_id: u64) -> Vec<u8> {
+    let address = m.escrow_address(order_id);
+    let escrow = m.bank.anchor_account::<SolEscrow>(&address);
+    delivery_attestation_message(&address, order_id, escrow.created_at, escrow.amount_lamports)
+}
+
+// The attestation followed by the release it authorizes, sent by a third party
+fn attested_release(s: &Setup, order_id: u64, signer: &Keypair, message: &[u8]) -> [Instruction; 2] {
+    let accounts = solmarket::accounts::ReleaseEscrow {
+        instructions: Some(sysvar::instructions::ID),
+        ..s.m.release_accounts(order_id, s.cranker)
+    };
+    let release = instruction(accounts, solmarket::instruction::ReleaseWithAttestation {});
+    [ed25519_verify(signer, message), release]
+}
+
+#[test]
+fn an_attestation_releases_to_the_merchant() {
+    let mut s = setup();
+    let escrow = s.m.open_escrow(1, AMOUNT);
+    let fee_bps = s.m.bank.anchor_account::<Config>(&s.m.config).fee_bps as u64;
+    let before = s.m.bank.lamports(&s.m.payout_wallet);
+    let held = s.m.bank.lamports(&escrow);
+
+    let message = attestation(&s.m, 1);
+    let ixs = attested_release(&s, 1, &s.attester, &message);
+    s.m.bank.process(&ixs, &[&s.cranker]).unwrap();
+
+    assert!(s.m.bank.account(&escrow).is_none());
+    // The order less the fee, plus the escrow's rent as it closes
+    let fee = AMOUNT * fee_bps / 10_000;
+    assert_eq!(s.m.bank.lamports(&s.m.payout_wallet), before + held - fee);
+}
+
+#[test]
+fn only_the_configured_attester_counts() {
+    let mut s = setup();
+    let escrow = s.m.open_escrow(1, AMOUNT);
+    let message = attestation(&s.m, 1);
+
+    let impostor = Keypair::new();
+    let ixs = attested_release(&s, 1, &impostor, &message);
+    let err = s.m.bank.process(&ixs, &[&s.cranker]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::InvalidSignature));
+
+    // The attester signing a different order does not help either
+    let mut other = message.clone();
+    other[32] ^= 1;
+    let ixs = attested_release(&s, 1, &s.attester, &other);
+    let err = s.m.bank.process(&ixs, &[&s.cranker]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::InvalidSignature));
+    assert!(s.m.bank.account(&escrow).is_some());
+}
+
+#[test]
+fn an_attestation_does_not_carry_over_to_a_recreated_escrow() {
+    let mut s = setup();
+    s.m.open_escrow(1, AMOUNT);
+    let message = attestation(&s.m, 1);
+    let ixs = attested_release(&s, 1, &s.attester, &message);
+    s.m.bank.process(&ixs, &[&s.cranker]).unwrap();
+
+    // Same buyer and order_id, so the same PDA, opened again later
+    s.m.bank.warp_to(s.m.bank.now() + DAY);
+    let escrow = s.m.open_escrow(1, AMOUNT);
+    let ixs = attested_release(&s, 1, &s.attester, &message);
+    let err = s.m.bank.process(&ixs, &[&s.cranker]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::InvalidSignature));
+    assert!(s.m.bank.account(&escrow).is_some());
+
+    // It takes a fresh attestation for the new escrow
+    let message = attestation(&s.m, 1);
+    let ixs = attested_release(&s, 1, &s.attester, &message);
+    s.m.bank.process(&ixs, &[&s.cranker]).unwrap();
+    assert!(s.m.bank.account(&escrow).is_none());
+}
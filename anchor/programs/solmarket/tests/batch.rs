@@ -0,0 +1,134 @@
+use anchor_lang::prelude::{AccountMeta, Pubkey};
+use anchor_lang::solana_program::{instruction::Instruction, native_token::LAMPORTS_PER_SOL};
+use solmarket::events::BatchEscrowSettled;
+use solmarket::handlers::EscrowError;
+use solmarket::state::{Config, EscrowStatus, SolEscrow, Treasury};
+use solmarket_test_runtime::market::{Market, DAY};
+use solmarket_test_runtime::{event_authority, instruction, program_error, with_remaining};
+
+const AMOUNT: u64 = LAMPORTS_PER_SOL;
+
+fn absent() -> AccountMeta {
+    AccountMeta::new_readonly(solmarket::ID, false)
+}
+
+fn batch_release(m: &Market, signer: Pubkey, order_ids: &[u64]) -> Instruction {
+    let ix = instruction(
+        solmarket::accounts::BatchRelease {
+            authority: signer,
+            config: m.config,
+            treasury: m.treasury,
+            event_authority: event_authority(),
+            program: solmarket::ID,
+        },
+        solmarket::instruction::BatchRelease {},
+    );
+    with_remaining(
+        ix,
+        order_ids.iter().flat_map(|order_id| {
+            [
+                AccountMeta::new(m.escrow_address(*order_id), false),
+                AccountMeta::new_readonly(m.merchant_account, false),
+                AccountMeta::new(m.payout_wallet, false),
+                absent(),
+                absent(),
+            ]
+        }),
+    )
+}
+
+fn batch_refund(m: &Market, signer: Pubkey, order_ids: &[u64]) -> Instruction {
+    let ix = instruction(
+        solmarket::accounts::BatchRefund {
+            authority: signer,
+            config: m.config,
+            event_authority: event_authority(),
+            program: solmarket::ID,
+        },
+        solmarket::instruction::BatchRefund {},
+    );
+    with_remaining(
+        ix,
+        order_ids
+            .iter()
+            .flat_map(|order_id| [AccountMeta::new(m.escrow_address(*order_id), false), AccountMeta::new(m.buyer, false), absent()]),
+    )
+}
+
+// Which escrows of the last batch were settled, in batch order
+fn settled(m: &Market) -> Vec<(u64, bool)> {
+    m.bank.events::<BatchEscrowSettled>().iter().map(|event| (event.order_id, event.settled)).collect()
+}
+
+#[test]
+fn batch_release_settles_what_it_can_and_reports_the_rest() {
+    let mut m = Market::new();
+    let released = m.open_escrow(1, AMOUNT);
+    // Still waiting for the buyer's funds
+    let expires_at = m.bank.now() + DAY;
+    m.bank.process(&[m.initialize_escrow(2, AMOUNT, expires_at)], &[&m.buyer]).unwrap();
+    // Frozen by a dispute
+    let disputed = m.open_escrow(3, AMOUNT);
+    m.bank.process(&[m.raise_dispute(3, m.buyer)], &[&m.buyer]).unwrap();
+
+    let fee = AMOUNT * m.bank.anchor_account::<Config>(&m.config).fee_bps as u64 / 10_000;
+    let payout_before = m.bank.lamports(&m.payout_wallet);
+    let rent = m.bank.lamports(&released) - AMOUNT;
+    m.bank.process(&[batch_release(&m, m.merchant, &[1, 2, 3])], &[&m.merchant]).unwrap();
+
+    assert_eq!(settled(&m), [(1, true), (2, false), (3, false)]);
+    let event = &m.bank.events::<BatchEscrowSettled>()[0];
+    assert_eq!((event.escrow, event.release, event.merchant_payout, event.fee), (released, true, AMOUNT - fee, fee));
+    assert!(m.bank.account(&released).is_none());
+    // The escrow's rent goes to the payout wallet along with the payout
+    assert_eq!(m.bank.lamports(&m.payout_wallet), payout_before + AMOUNT - fee + rent);
+    assert_eq!(m.bank.anchor_account::<Treasury>(&m.treasury).total_fees, fee);
+    assert_eq!(m.bank.anchor_account::<SolEscrow>(&m.escrow_address(2)).status, EscrowStatus::Pending as u8);
+    assert_eq!(m.bank.anchor_account::<SolEscrow>(&disputed).status, EscrowStatus::Disputed as u8);
+}
+
+#[test]
+fn batch_refund_skips_escrows_the_signer_cannot_refund() {
+    let mut m = Market::new();
+    let funded = m.open_escrow(1, AMOUNT);
+    // Once shipped the buyer has to dispute instead
+    let shipped = m.open_escrow(2, AMOUNT);
+    m.bank.process(&[m.mark_shipped(2)], &[&m.merchant]).unwrap();
+
+    // The merchant cannot refund anything before the grace window closes
+    m.bank.process(&[batch_refund(&m, m.merchant, &[1, 2])], &[&m.merchant]).unwrap();
+    assert_eq!(settled(&m), [(1, false), (2, false)]);
+    assert!(m.bank.account(&funded).is_some());
+
+    let before = m.bank.lamports(&m.buyer);
+    let held = m.bank.lamports(&funded);
+    m.bank.process(&[batch_refund(&m, m.buyer, &[1, 2])], &[&m.buyer]).unwrap();
+    assert_eq!(settled(&m), [(1, true), (2, false)]);
+    assert_eq!(m.bank.events::<BatchEscrowSettled>()[0].refunded_lamports, AMOUNT);
+    assert!(m.bank.account(&funded).is_none());
+    assert_eq!(m.bank.lamports(&m.buyer), before + held);
+    assert_eq!(m.bank.anchor_account::<SolEscrow>(&shipped).status, EscrowStatus::Shipped as u8);
+}
+
+#[test]
+fn a_malformed_batch_fails_as_a_whole() {
+    let mut m = Market::new();
+    let escrow = m.open_escrow(1, AMOUNT);
+    m.open_escrow(2, AMOUNT);
+
+    // A group cut short
+    let mut ix = batch_release(&m, m.merchant, &[1, 2]);
+    ix.accounts.pop();
+    let err = m.bank.process(&[ix], &[&m.merchant]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::InvalidBatch));
+
+    // A payout wallet other than the merchant's, even on the second escrow
+    let mut ix = batch_release(&m, m.merchant, &[1, 2]);
+    let stranger = Pubkey::new_unique();
+    m.bank.airdrop(&stranger, LAMPORTS_PER_SOL);
+    let last_payout = ix.accounts.len() - 3;
+    ix.accounts[last_payout].pubkey = stranger;
+    let err = m.bank.process(&[ix], &[&m.merchant]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::InvalidBatch));
+    assert_eq!(m.bank.anchor_account::<SolEscrow>(&escrow).status, EscrowStatus::Funded as u8);
+}
@@ -0,0 +1,186 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::{instruction::Instruction, native_token::LAMPORTS_PER_SOL, system_program};
+use anchor_spl::associated_token::{self, get_associated_token_address_with_program_id};
+use anchor_spl::token::spl_token;
+use solmarket::handlers::EscrowError;
+use solmarket::state::{Config, MerchantBond};
+use solmarket_test_runtime::market::{Market, DAY};
+use solmarket_test_runtime::token::{create_mint, create_token_account, mint_to, DECIMALS};
+use solmarket_test_runtime::{event_authority, instruction, pda, program_error};
+
+const AMOUNT: u64 = LAMPORTS_PER_SOL;
+
+fn bond_address(m: &Market) -> Pubkey {
+    pda(&[b"merchant-bond", m.merchant.as_ref()])
+}
+
+fn post(m: &Market, lamports: u64) -> Instruction {
+    instruction(
+        solmarket::accounts::PostBond {
+            wallet: m.merchant,
+            system_program: system_program::ID,
+            merchant: m.merchant_account,
+            bond: bond_address(m),
+            event_authority: event_authority(),
+            program: solmarket::ID,
+        },
+        solmarket::instruction::PostBond { lamports },
+    )
+}
+
+fn request_withdrawal(m: &Market, lamports: u64) -> Instruction {
+    instruction(
+        solmarket::accounts::RequestBondWithdrawal {
+            wallet: m.merchant,
+            config: m.config,
+            bond: bond_address(m),
+            event_authority: event_authority(),
+            program: solmarket::ID,
+        },
+        solmarket::instruction::RequestBondWithdrawal { lamports, tokens: 0 },
+    )
+}
+
+fn withdraw(m: &Market) -> Instruction {
+    instruction(
+        solmarket::accounts::WithdrawBond {
+            wallet: m.merchant,
+            config: m.config,
+            bond: bond_address(m),
+            event_authority: event_authority(),
+            program: solmarket::ID,
+        },
+        solmarket::instruction::WithdrawBond {},
+    )
+}
+
+fn slash(m: &Market, signer: Pubkey, lamports: u64) -> Instruction {
+    instruction(
+        solmarket::accounts::SlashBond {
+            authority: signer,
+            config: m.config,
+            recipient: m.buyer,
+            bond: bond_address(m),
+            event_authority: event_authority(),
+            program: solmarket::ID,
+        },
+        solmarket::instruction::SlashBond { lamports },
+    )
+}
+
+// Create order `order_id` for AMOUNT against the merchant's bond
+fn create_bonded(m: &Market, order_id: u64) -> Instruction {
+    instruction(
+        solmarket::accounts::InitializeEscrow { bond: Some(bond_address(m)), ..m.initialize_accounts(order_id) },
+        m.escrow_args(order_id, AMOUNT, m.bank.now() + DAY),
+    )
+}
+
+#[test]
+fn orders_need_a_bond_of_min_bond_bps_once_it_is_set() {
+    let mut m = Market::new();
+    m.configure(solmarket::instruction::SetMinBondBps { min_bond_bps: 1_000 });
+    let err = m.bank.process(&[m.initialize_escrow(1, AMOUNT, m.bank.now() + DAY)], &[&m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::BondTooLow));
+
+    m.bank.process(&[post(&m, AMOUNT / 10 - 1)], &[&m.merchant]).unwrap();
+    let err = m.bank.process(&[create_bonded(&m, 1)], &[&m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::BondTooLow));
+
+    // Topping up adds to the same bond
+    m.bank.process(&[post(&m, 1)], &[&m.merchant]).unwrap();
+    assert_eq!(m.bank.anchor_account::<MerchantBond>(&bond_address(&m)).lamports, AMOUNT / 10);
+    m.bank.process(&[create_bonded(&m, 1)], &[&m.buyer]).unwrap();
+
+    // Lamports requested for withdrawal no longer count
+    m.bank.process(&[request_withdrawal(&m, 1)], &[&m.merchant]).unwrap();
+    let err = m.bank.process(&[create_bonded(&m, 2)], &[&m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::BondTooLow));
+}
+
+#[test]
+fn withdrawals_wait_out_the_timelock() {
+    let mut m = Market::new();
+    m.bank.process(&[post(&m, AMOUNT)], &[&m.merchant]).unwrap();
+    let err = m.bank.process(&[withdraw(&m)], &[&m.merchant]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::BondLocked));
+
+    m.bank.process(&[request_withdrawal(&m, AMOUNT / 4)], &[&m.merchant]).unwrap();
+    let delay = m.bank.anchor_account::<Config>(&m.config).bond_withdraw_delay_secs;
+    m.bank.warp_to(m.bank.now() + delay - 1);
+    let err = m.bank.process(&[withdraw(&m)], &[&m.merchant]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::BondLocked));
+
+    m.bank.warp_to(m.bank.now() + 1);
+    let before = m.bank.lamports(&m.merchant);
+    m.bank.process(&[withdraw(&m)], &[&m.merchant]).unwrap();
+    assert_eq!(m.bank.lamports(&m.merchant), before + AMOUNT / 4);
+    let bond = m.bank.anchor_account::<MerchantBond>(&bond_address(&m));
+    assert_eq!((bond.lamports, bond.pending_lamports, bond.withdraw_requested_at), (AMOUNT * 3 / 4, 0, 0));
+}
+
+#[test]
+fn slashing_pays_the_buyer_ahead_of_a_pending_withdrawal() {
+    let mut m = Market::new();
+    m.bank.process(&[post(&m, AMOUNT)], &[&m.merchant]).unwrap();
+    m.bank.process(&[request_withdrawal(&m, AMOUNT)], &[&m.merchant]).unwrap();
+
+    let err = m.bank.process(&[slash(&m, m.merchant, AMOUNT)], &[&m.merchant]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::Unauthorized));
+    let err = m.bank.process(&[slash(&m, m.authority, AMOUNT + 1)], &[&m.authority]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::InvalidAmount));
+
+    let buyer_before = m.bank.lamports(&m.buyer);
+    m.bank.process(&[slash(&m, m.authority, AMOUNT * 7 / 10)], &[&m.authority]).unwrap();
+    assert_eq!(m.bank.lamports(&m.buyer), buyer_before + AMOUNT * 7 / 10);
+    let bond = m.bank.anchor_account::<MerchantBond>(&bond_address(&m));
+    assert_eq!((bond.lamports, bond.pending_lamports, bond.total_slashed_lamports), (AMOUNT * 3 / 10, AMOUNT * 3 / 10, AMOUNT * 7 / 10));
+
+    // The merchant only gets back what slashing left
+    let delay = m.bank.anchor_account::<Config>(&m.config).bond_withdraw_delay_secs;
+    m.bank.warp_to(m.bank.now() + delay);
+    let before = m.bank.lamports(&m.merchant);
+    m.bank.process(&[withdraw(&m)], &[&m.merchant]).unwrap();
+    assert_eq!(m.bank.lamports(&m.merchant), before + AMOUNT * 3 / 10);
+}
+
+#[test]
+fn token_bonds_count_at_the_configured_rate() {
+    let mut m = Market::new();
+    let mint = create_mint(&mut m.bank, &m.authority, &spl_token::ID, None);
+    let merchant = m.merchant;
+    let tokens = create_token_account(&mut m.bank, &merchant, &merchant, &mint, &spl_token::ID);
+    let whole = 10u64.pow(DECIMALS as u32);
+    mint_to(&mut m.bank, &m.authority, &mint, &tokens, &spl_token::ID, 10 * whole);
+    m.configure(solmarket::instruction::SetBondMint { bond_mint: mint });
+    // One whole token is worth 0.01 SOL, so ten cover a 10% bond on AMOUNT
+    m.configure(solmarket::instruction::SetBondTokenRate { bond_token_rate: AMOUNT / 100 });
+    m.configure(solmarket::instruction::SetMinBondBps { min_bond_bps: 1_000 });
+
+    let bond = bond_address(&m);
+    let post_tokens = |amount| {
+        instruction(
+            solmarket::accounts::PostBondTokens {
+                associated_token_program: associated_token::ID,
+                token_program: spl_token::ID,
+                system_program: system_program::ID,
+                wallet: merchant,
+                config: m.config,
+                merchant: m.merchant_account,
+                mint,
+                wallet_token_account: tokens,
+                bond,
+                vault: get_associated_token_address_with_program_id(&bond, &mint, &spl_token::ID),
+                event_authority: event_authority(),
+                program: solmarket::ID,
+            },
+            solmarket::instruction::PostBondTokens { amount },
+        )
+    };
+    m.bank.process(&[post_tokens(9 * whole)], &[&merchant]).unwrap();
+    let err = m.bank.process(&[create_bonded(&m, 1)], &[&m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::BondTooLow));
+
+    m.bank.process(&[post_tokens(whole)], &[&merchant]).unwrap();
+    m.bank.process(&[create_bonded(&m, 1)], &[&m.buyer]).unwrap();
+}
@@ -0,0 +1,201 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::{instruction::Instruction, native_token::LAMPORTS_PER_SOL, system_program};
+use solmarket::handlers::EscrowError;
+use solmarket::state::{BuyerStats, Config, SolEscrow};
+use solmarket_test_runtime::market::{Market, DAY};
+use solmarket_test_runtime::{event_authority, instruction, pda, program_error};
+
+const AMOUNT: u64 = LAMPORTS_PER_SOL;
+
+// Buyer caps as given, with the buyer's stats opened
+fn setup(max_open_escrows_per_buyer: u32, max_buyer_exposure_lamports: u64) -> (Market, Pubkey) {
+    let mut m = Market::new();
+    let buyer_stats = pda(&[b"buyer-stats", m.buyer.as_ref()]);
+    let caps = instruction(
+        solmarket::accounts::UpdateConfig { authority: m.authority, config: m.config },
+        solmarket::instruction::SetBuyerCaps { max_open_escrows_per_buyer, max_buyer_exposure_lamports },
+    );
+    m.bank.process(&[caps], &[&m.authority]).unwrap();
+    let stats = instruction(
+        solmarket::accounts::InitializeBuyerStats {
+            payer: m.buyer,
+            system_program: system_program::ID,
+            buyer: m.buyer,
+            buyer_stats,
+        },
+        solmarket::instruction::InitializeBuyerStats {},
+    );
+    m.bank.process(&[stats], &[&m.buyer]).unwrap();
+    (m, buyer_stats)
+}
+
+// An escrow for the market's buyer, created (and its rent paid) by `payer`
+fn create_for_buyer(m: &mut Market, payer: Pubkey, order_id: u64) {
+    let ix = instruction(
+        solmarket::accounts::InitializeEscrow { payer, ..m.initialize_accounts(order_id) },
+        m.escrow_args(order_id, AMOUNT, m.bank.now() + DAY),
+    );
+    m.bank.process(&[ix], &[&payer]).unwrap();
+}
+
+fn fund(m: &Market, funder: Pubkey, order_id: u64, buyer_stats: Option<Pubkey>) -> Instruction {
+    instruction(
+        solmarket::accounts::FundEscrow {
+            buyer: funder,
+            system_program: system_program::ID,
+            config: m.config,
+            buyer_stats,
+            escrow: m.escrow_address(order_id),
+            event_authority: event_authority(),
+            program: solmarket::ID,
+        },
+        solmarket::instruction::FundEscrow {},
+    )
+}
+
+// Create and fund order `order_id` for AMOUNT as the buyer, tracked in their stats
+fn open_tracked(m: &mut Market, order_id: u64, buyer_stats: Pubkey) {
+    create_for_buyer(m, m.buyer, order_id);
+    m.bank.process(&[fund(m, m.buyer, order_id, Some(buyer_stats))], &[&m.buyer]).unwrap();
+}
+
+fn stats(m: &Market, buyer_stats: Pubkey) -> (u32, u64) {
+    let stats = m.bank.anchor_account::<BuyerStats>(&buyer_stats);
+    (stats.open_escrows, stats.lamports_in_flight)
+}
+
+#[test]
+fn order_amounts_stay_within_the_configured_limits() {
+    let mut m = Market::new();
+    let expires_at = m.bank.now() + DAY;
+    let inverted = instruction(
+        solmarket::accounts::UpdateConfig { authority: m.authority, config: m.config },
+        solmarket::instruction::SetOrderLimits { min_order_lamports: AMOUNT + 1, max_order_lamports: AMOUNT },
+    );
+    let err = m.bank.process(&[inverted], &[&m.authority]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::InvalidAmount));
+    m.configure(solmarket::instruction::SetOrderLimits { min_order_lamports: AMOUNT / 2, max_order_lamports: AMOUNT });
+
+    let err = m.bank.process(&[m.initialize_escrow(1, AMOUNT / 2 - 1, expires_at)], &[&m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::AmountBelowMinimum));
+    let err = m.bank.process(&[m.initialize_escrow(1, AMOUNT + 1, expires_at)], &[&m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::AmountAboveMaximum));
+    m.bank.process(&[m.initialize_escrow(1, AMOUNT / 2, expires_at)], &[&m.buyer]).unwrap();
+    m.bank.process(&[m.initialize_escrow(2, AMOUNT, expires_at)], &[&m.buyer]).unwrap();
+
+    // A maximum of 0 lifts the upper bound
+    m.configure(solmarket::instruction::SetOrderLimits { min_order_lamports: AMOUNT / 2, max_order_lamports: 0 });
+    m.bank.process(&[m.initialize_escrow(3, 100 * AMOUNT, expires_at)], &[&m.buyer]).unwrap();
+}
+
+#[test]
+fn expiry_is_capped_at_the_max_escrow_duration() {
+    let mut m = Market::new();
+    let max = m.bank.anchor_account::<Config>(&m.config).max_escrow_duration_secs;
+    let err = m.bank.process(&[m.initialize_escrow(1, AMOUNT, m.bank.now() + max + 1)], &[&m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::DurationTooLong));
+    m.bank.process(&[m.initialize_escrow(1, AMOUNT, m.bank.now() + max)], &[&m.buyer]).unwrap();
+
+    m.configure(solmarket::instruction::SetMaxEscrowDuration { max_escrow_duration_secs: DAY });
+    let err = m.bank.process(&[m.initialize_escrow(2, AMOUNT, m.bank.now() + DAY + 1)], &[&m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::DurationTooLong));
+}
+
+#[test]
+fn funding_stops_at_the_exposure_ceiling() {
+    let (mut m, buyer_stats) = setup(0, 2 * AMOUNT + AMOUNT / 2);
+    open_tracked(&mut m, 1, buyer_stats);
+    open_tracked(&mut m, 2, buyer_stats);
+    let buyer = m.buyer;
+    create_for_buyer(&mut m, buyer, 3);
+    let err = m.bank.process(&[fund(&m, m.buyer, 3, Some(buyer_stats))], &[&m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::ExposureLimitExceeded));
+    assert_eq!(stats(&m, buyer_stats), (2, 2 * AMOUNT));
+
+    // Settling an order frees its amount for the next one
+    let release = instruction(
+        solmarket::accounts::ReleaseEscrow { buyer_stats: Some(buyer_stats), ..m.release_accounts(1, m.merchant) },
+        solmarket::instruction::ReleaseEscrow {},
+    );
+    m.bank.process(&[release], &[&m.merchant]).unwrap();
+    assert_eq!(stats(&m, buyer_stats), (1, AMOUNT));
+    m.bank.process(&[fund(&m, m.buyer, 3, Some(buyer_stats))], &[&m.buyer]).unwrap();
+    assert_eq!(stats(&m, buyer_stats), (2, 2 * AMOUNT));
+}
+
+#[test]
+fn refunds_expiries_and_cancellations_release_exposure() {
+    let (mut m, buyer_stats) = setup(3, 3 * AMOUNT);
+    for order_id in 1..=3 {
+        open_tracked(&mut m, order_id, buyer_stats);
+    }
+    assert_eq!(stats(&m, buyer_stats), (3, 3 * AMOUNT));
+
+    // A tracked escrow cannot settle without the stats it counts against
+    let err = m.bank.process(&[m.refund_escrow(1, m.buyer)], &[&m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::BuyerStatsMissing));
+    let refund = instruction(
+        solmarket::accounts::RefundEscrow { buyer_stats: Some(buyer_stats), ..m.refund_accounts(1, m.buyer) },
+        solmarket::instruction::RefundEscrow {},
+    );
+    m.bank.process(&[refund], &[&m.buyer]).unwrap();
+    assert_eq!(stats(&m, buyer_stats), (2, 2 * AMOUNT));
+
+    let cancel = instruction(
+        solmarket::accounts::MutualCancel { buyer_stats: Some(buyer_stats), ..m.mutual_cancel_accounts(2, None) },
+        solmarket::instruction::MutualCancel {},
+    );
+    m.bank.process(&[cancel], &[&m.buyer, &m.merchant]).unwrap();
+    assert_eq!(stats(&m, buyer_stats), (1, AMOUNT));
+
+    let escrow = m.bank.anchor_account::<SolEscrow>(&m.escrow_address(3));
+    m.bank.warp_to(escrow.expires_at + m.bank.anchor_account::<Config>(&m.config).expiry_grace_secs);
+    let expire = instruction(
+        solmarket::accounts::ExpireEscrow {
+            system_program: system_program::ID,
+            config: m.config,
+            buyer_account: m.buyer,
+            payer: m.buyer,
+            buyer_stats: Some(buyer_stats),
+            escrow: m.escrow_address(3),
+            event_authority: event_authority(),
+            program: solmarket::ID,
+        },
+        solmarket::instruction::ExpireEscrow {},
+    );
+    m.bank.process(&[expire], &[]).unwrap();
+    assert_eq!(stats(&m, buyer_stats), (0, 0));
+}
+
+#[test]
+fn only_funding_by_the_buyer_counts_against_their_caps() {
+    let (mut m, buyer_stats) = setup(2, 0);
+    let stranger = Pubkey::new_unique();
+    m.bank.airdrop(&stranger, 10 * LAMPORTS_PER_SOL);
+
+    // Creating escrows in the buyer's name takes nothing from their caps
+    for order_id in 1..=3 {
+        create_for_buyer(&mut m, stranger, order_id);
+    }
+    let stats = m.bank.anchor_account::<BuyerStats>(&buyer_stats);
+    assert_eq!(stats.open_escrows, 0);
+    assert_eq!(stats.lamports_in_flight, 0);
+
+    // Nor can a stranger fund one into them, with or without the stats
+    let err = m.bank.process(&[fund(&m, stranger, 1, Some(buyer_stats))], &[&stranger]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::Unauthorized));
+    let err = m.bank.process(&[fund(&m, stranger, 1, None)], &[&stranger]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::BuyerStatsMissing));
+
+    // The buyer funding their own escrows is what counts, up to the cap
+    for order_id in 1..=2 {
+        m.bank.process(&[fund(&m, m.buyer, order_id, Some(buyer_stats))], &[&m.buyer]).unwrap();
+        assert!(m.bank.anchor_account::<SolEscrow>(&m.escrow_address(order_id)).tracked_in_buyer_stats);
+    }
+    let stats = m.bank.anchor_account::<BuyerStats>(&buyer_stats);
+    assert_eq!(stats.open_escrows, 2);
+    assert_eq!(stats.lamports_in_flight, 2 * AMOUNT);
+
+    let err = m.bank.process(&[fund(&m, m.buyer, 3, Some(buyer_stats))], &[&m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::TooManyOpenEscrows));
+}
@@ -0,0 +1,73 @@
+use anchor_lang::prelude::{ProgramError, Pubkey};
+use anchor_lang::solana_program::{instruction::Instruction, native_token::LAMPORTS_PER_SOL};
+use solmarket::handlers::EscrowError;
+use solmarket::state::{EscrowStatus, SolEscrow};
+use solmarket_test_runtime::market::{Market, DAY};
+use solmarket_test_runtime::{event_authority, instruction, program_error};
+
+const AMOUNT: u64 = LAMPORTS_PER_SOL;
+
+fn decline(m: &Market, order_id: u64, signer: Pubkey) -> Instruction {
+    instruction(
+        solmarket::accounts::DeclineOrder {
+            merchant: signer,
+            config: m.config,
+            buyer_account: m.buyer,
+            payer: m.buyer,
+            buyer_stats: None,
+            escrow: m.escrow_address(order_id),
+            event_authority: event_authority(),
+            program: solmarket::ID,
+        },
+        solmarket::instruction::DeclineOrder { reason_code: 3 },
+    )
+}
+
+#[test]
+fn the_merchant_declines_pending_and_funded_orders() {
+    let mut m = Market::new();
+    let funded = m.open_escrow(1, AMOUNT);
+    let expires_at = m.bank.now() + DAY;
+    m.bank.process(&[m.initialize_escrow(2, AMOUNT, expires_at)], &[&m.buyer]).unwrap();
+    let pending = m.escrow_address(2);
+
+    let err = m.bank.process(&[decline(&m, 1, m.buyer)], &[&m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::Unauthorized));
+
+    // The buyer paid the rent too, so everything either escrow holds comes back
+    let before = m.bank.lamports(&m.buyer);
+    let held = m.bank.lamports(&funded) + m.bank.lamports(&pending);
+    m.bank.process(&[decline(&m, 1, m.merchant), decline(&m, 2, m.merchant)], &[&m.merchant]).unwrap();
+    assert!(m.bank.account(&funded).is_none() && m.bank.account(&pending).is_none());
+    assert_eq!(m.bank.lamports(&m.buyer), before + held);
+}
+
+#[test]
+fn a_shipped_order_can_no_longer_be_declined() {
+    let mut m = Market::new();
+    let escrow = m.open_escrow(1, AMOUNT);
+    m.bank.process(&[m.mark_shipped(1)], &[&m.merchant]).unwrap();
+    let err = m.bank.process(&[decline(&m, 1, m.merchant)], &[&m.merchant]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::InvalidState));
+    assert_eq!(m.bank.anchor_account::<SolEscrow>(&escrow).status, EscrowStatus::Shipped as u8);
+}
+
+#[test]
+fn mutual_cancel_needs_both_parties_and_refunds_a_shipped_order() {
+    let mut m = Market::new();
+    let escrow = m.open_escrow(1, AMOUNT);
+    m.bank.process(&[m.mark_shipped(1)], &[&m.merchant]).unwrap();
+
+    // Either party alone cannot call it off
+    let err = m.bank.process(&[m.mutual_cancel(1, None)], &[&m.buyer]).unwrap_err();
+    assert_eq!(err, ProgramError::MissingRequiredSignature);
+    let err = m.bank.process(&[m.mutual_cancel(1, None)], &[&m.merchant]).unwrap_err();
+    assert_eq!(err, ProgramError::MissingRequiredSignature);
+    assert_eq!(m.bank.anchor_account::<SolEscrow>(&escrow).status, EscrowStatus::Shipped as u8);
+
+    let before = m.bank.lamports(&m.buyer);
+    let held = m.bank.lamports(&escrow);
+    m.bank.process(&[m.mutual_cancel(1, None)], &[&m.buyer, &m.merchant]).unwrap();
+    assert!(m.bank.account(&escrow).is_none());
+    assert_eq!(m.bank.lamports(&m.buyer), before + held);
+}
@@ -0,0 +1,200 @@
+use anchor_lang::prelude::{AccountMeta, Pubkey};
+use anchor_lang::solana_program::{instruction::Instruction, native_token::LAMPORTS_PER_SOL, system_program};
+use solmarket::events::CartItemSettled;
+use solmarket::handlers::EscrowError;
+use solmarket::state::{CartEscrow, CartLine, Config, EscrowStatus, Treasury, PAUSE_REFUND};
+use solmarket_test_runtime::market::{Market, DAY};
+use solmarket_test_runtime::{event_authority, instruction, pda, program_error, with_remaining};
+
+const FIRST: u64 = LAMPORTS_PER_SOL;
+const SECOND: u64 = LAMPORTS_PER_SOL / 2;
+
+// The market's merchant sells line 0, a second merchant line 1
+struct Setup {
+    m: Market,
+    other: Pubkey,
+    other_account: Pubkey,
+    other_payout: Pubkey,
+    cart: Pubkey,
+}
+
+// Cart 1 with a line for each merchant, their Merchant accounts passed in line order
+fn initialize(m: &Market, other: Pubkey, other_account: Pubkey) -> Instruction {
+    let ix = instruction(
+        solmarket::accounts::InitializeCart {
+            payer: m.buyer,
+            system_program: system_program::ID,
+            config: m.config,
+            buyer: m.buyer,
+            cart: cart_address(m),
+            event_authority: event_authority(),
+            program: solmarket::ID,
+        },
+        solmarket::instruction::InitializeCart {
+            cart_id: 1,
+            expires_at: m.bank.now() + DAY,
+            lines: vec![
+                CartLine { merchant: m.merchant, amount_lamports: FIRST },
+                CartLine { merchant: other, amount_lamports: SECOND },
+            ],
+        },
+    );
+    with_remaining(
+        ix,
+        [AccountMeta::new_readonly(m.merchant_account, false), AccountMeta::new_readonly(other_account, false)],
+    )
+}
+
+fn cart_address(m: &Market) -> Pubkey {
+    pda(&[b"cart-escrow", m.buyer.as_ref(), &1u64.to_le_bytes()])
+}
+
+fn setup() -> Setup {
+    let mut m = Market::new();
+    let (other, other_payout) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let other_account = m.register_merchant(other, other_payout);
+    m.bank.airdrop(&other_payout, LAMPORTS_PER_SOL);
+    m.bank.process(&[initialize(&m, other, other_account)], &[&m.buyer]).unwrap();
+    let cart = cart_address(&m);
+    Setup { m, other, other_account, other_payout, cart }
+}
+
+impl Setup {
+    fn fund(&self) -> Instruction {
+        instruction(
+            solmarket::accounts::FundCart {
+                buyer: self.m.buyer,
+                system_program: system_program::ID,
+                config: self.m.config,
+                cart: self.cart,
+                event_authority: event_authority(),
+                program: solmarket::ID,
+            },
+            solmarket::instruction::FundCart {},
+        )
+    }
+
+    // Release line `index`, signed by `signer`, to the merchant behind `merchant_account`
+    fn release(&self, index: u8, signer: Pubkey, merchant_account: Pubkey, payout_wallet: Pubkey) -> Instruction {
+        instruction(
+            solmarket::accounts::ReleaseCartItem {
+                authority: signer,
+                config: self.m.config,
+                merchant_account,
+                payout_wallet,
+                treasury: self.m.treasury,
+                payer: self.m.buyer,
+                cart: self.cart,
+                event_authority: event_authority(),
+                program: solmarket::ID,
+            },
+            solmarket::instruction::ReleaseCartItem { index },
+        )
+    }
+
+    fn refund(&self, index: u8, signer: Pubkey) -> Instruction {
+        instruction(
+            solmarket::accounts::RefundCartItem {
+                authority: signer,
+                config: self.m.config,
+                buyer_account: self.m.buyer,
+                payer: self.m.buyer,
+                cart: self.cart,
+                event_authority: event_authority(),
+                program: solmarket::ID,
+            },
+            solmarket::instruction::RefundCartItem { index },
+        )
+    }
+
+    fn line_status(&self, index: usize) -> u8 {
+        self.m.bank.anchor_account::<CartEscrow>(&self.cart).items[index].status
+    }
+}
+
+#[test]
+fn each_line_settles_on_its_own_and_the_last_closes_the_cart() {
+    let mut s = setup();
+    s.m.bank.process(&[s.fund()], &[&s.m.buyer]).unwrap();
+    let rent = s.m.bank.lamports(&s.cart) - FIRST - SECOND;
+
+    // A merchant only releases their own line
+    let err = s.m.bank.process(&[s.release(1, s.m.merchant, s.m.merchant_account, s.m.payout_wallet)], &[&s.m.merchant]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::InvalidCart));
+    let err = s.m.bank.process(&[s.release(0, s.other, s.m.merchant_account, s.m.payout_wallet)], &[&s.other]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::Unauthorized));
+
+    let fee_bps = s.m.bank.anchor_account::<Config>(&s.m.config).fee_bps as u64;
+    let payout_before = s.m.bank.lamports(&s.m.payout_wallet);
+    s.m.bank.process(&[s.release(0, s.m.merchant, s.m.merchant_account, s.m.payout_wallet)], &[&s.m.merchant]).unwrap();
+    let fee = FIRST * fee_bps / 10_000;
+    assert_eq!(s.m.bank.lamports(&s.m.payout_wallet), payout_before + FIRST - fee);
+    assert_eq!(s.m.bank.anchor_account::<Treasury>(&s.m.treasury).total_fees, fee);
+    let event = &s.m.bank.events::<CartItemSettled>()[0];
+    assert_eq!((event.index, event.merchant_payout, event.fee), (0, FIRST - fee, fee));
+    assert_eq!((s.line_status(0), s.line_status(1)), (EscrowStatus::Released as u8, EscrowStatus::Funded as u8));
+
+    // A released line stays released; refunding the other one pays its merchant nothing
+    let err = s.m.bank.process(&[s.release(0, s.m.merchant, s.m.merchant_account, s.m.payout_wallet)], &[&s.m.merchant]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::InvalidState));
+    let buyer_before = s.m.bank.lamports(&s.m.buyer);
+    let other_before = s.m.bank.lamports(&s.other_payout);
+    s.m.bank.process(&[s.refund(1, s.m.buyer)], &[&s.m.buyer]).unwrap();
+    assert_eq!(s.m.bank.events::<CartItemSettled>()[0].refunded_lamports, SECOND);
+    assert_eq!(s.m.bank.lamports(&s.other_payout), other_before);
+    // Every line is settled, so the rent comes back to the payer with the refund
+    assert!(s.m.bank.account(&s.cart).is_none());
+    assert_eq!(s.m.bank.lamports(&s.m.buyer), buyer_before + SECOND + rent);
+}
+
+#[test]
+fn a_line_refunded_before_funding_is_not_charged() {
+    let mut s = setup();
+    s.m.bank.process(&[s.refund(1, s.m.buyer)], &[&s.m.buyer]).unwrap();
+    assert_eq!(s.m.bank.events::<CartItemSettled>()[0].refunded_lamports, 0);
+
+    let before = s.m.bank.lamports(&s.m.buyer);
+    s.m.bank.process(&[s.fund()], &[&s.m.buyer]).unwrap();
+    assert_eq!(s.m.bank.lamports(&s.m.buyer), before - FIRST);
+    assert_eq!((s.line_status(0), s.line_status(1)), (EscrowStatus::Funded as u8, EscrowStatus::Refunded as u8));
+
+    let payout_before = s.m.bank.lamports(&s.other_payout);
+    let err = s.m.bank.process(&[s.release(1, s.other, s.other_account, s.other_payout)], &[&s.other]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::InvalidState));
+    assert_eq!(s.m.bank.lamports(&s.other_payout), payout_before);
+}
+
+#[test]
+fn the_buyer_still_refunds_a_line_with_refunds_paused() {
+    let mut s = setup();
+    s.m.bank.process(&[s.fund()], &[&s.m.buyer]).unwrap();
+    s.m.set_paused(PAUSE_REFUND);
+    let err = s.m.bank.process(&[s.refund(0, s.m.authority)], &[&s.m.authority]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::Paused));
+
+    let before = s.m.bank.lamports(&s.m.buyer);
+    s.m.bank.process(&[s.refund(0, s.m.buyer)], &[&s.m.buyer]).unwrap();
+    assert_eq!(s.m.bank.lamports(&s.m.buyer), before + FIRST);
+    assert_eq!(s.line_status(1), EscrowStatus::Funded as u8);
+}
+
+#[test]
+fn every_line_needs_a_verified_merchant_once_required() {
+    let mut m = Market::new();
+    let (other, other_payout) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let other_account = m.register_merchant(other, other_payout);
+    m.configure(solmarket::instruction::SetRequireVerifiedMerchants { require_verified_merchants: true });
+    let verify = |merchant| {
+        instruction(
+            solmarket::accounts::UpdateMerchant { authority: m.authority, config: m.config, merchant },
+            solmarket::instruction::SetMerchantVerified { verified: true },
+        )
+    };
+    m.bank.process(&[verify(m.merchant_account)], &[&m.authority]).unwrap();
+    let err = m.bank.process(&[initialize(&m, other, other_account)], &[&m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::MerchantNotVerified));
+
+    m.bank.process(&[verify(other_account)], &[&m.authority]).unwrap();
+    m.bank.process(&[initialize(&m, other, other_account)], &[&m.buyer]).unwrap();
+    assert!(m.bank.account(&cart_address(&m)).is_some());
+}
@@ -0,0 +1,134 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::{instruction::Instruction, native_token::LAMPORTS_PER_SOL};
+use solmarket::handlers::EscrowError;
+use solmarket::state::{Config, Dispute, EscrowStatus, SolEscrow, Treasury};
+use solmarket_test_runtime::market::{Market, DAY};
+use solmarket_test_runtime::{event_authority, instruction, program_error};
+
+const AMOUNT: u64 = LAMPORTS_PER_SOL;
+
+struct Setup {
+    m: Market,
+    arbiter: Pubkey,
+}
+
+fn setup() -> Setup {
+    let mut m = Market::new();
+    let arbiter = Pubkey::new_unique();
+    let ix = instruction(
+        solmarket::accounts::UpdateConfig { authority: m.authority, config: m.config },
+        solmarket::instruction::SetArbiter { arbiter },
+    );
+    m.bank.process(&[ix], &[&m.authority]).unwrap();
+    Setup { m, arbiter }
+}
+
+fn resolve(m: &Market, order_id: u64, arbiter: Pubkey, buyer_bps: u16) -> Instruction {
+    instruction(
+        solmarket::accounts::ResolveDispute {
+            arbiter,
+            config: m.config,
+            buyer_account: m.buyer,
+            merchant_account: m.merchant_account,
+            payout_wallet: m.payout_wallet,
+            treasury: m.treasury,
+            referrer: None,
+            buyer_stats: None,
+            escrow: m.escrow_address(order_id),
+            dispute: m.dispute_address(order_id),
+            event_authority: event_authority(),
+            program: solmarket::ID,
+        },
+        solmarket::instruction::ResolveDispute { buyer_bps },
+    )
+}
+
+#[test]
+fn either_party_can_raise_and_it_freezes_the_escrow() {
+    let Setup { mut m, .. } = setup();
+    let escrow = m.open_escrow(1, AMOUNT);
+    let stranger = Pubkey::new_unique();
+    m.bank.airdrop(&stranger, LAMPORTS_PER_SOL);
+    let err = m.bank.process(&[m.raise_dispute(1, stranger)], &[&stranger]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::Unauthorized));
+
+    let dispute = m.dispute_address(1);
+    m.bank.process(&[m.raise_dispute(1, m.merchant)], &[&m.merchant]).unwrap();
+    assert_eq!(m.bank.anchor_account::<SolEscrow>(&escrow).status, EscrowStatus::Disputed as u8);
+    let record = m.bank.anchor_account::<Dispute>(&dispute);
+    assert_eq!((record.escrow, record.order_id, record.raised_by), (escrow, 1, m.merchant));
+    assert_eq!((record.raised_at, record.resolved_at), (m.bank.now(), 0));
+
+    // Only the arbiter, or both parties together, can move the funds now
+    let err = m.bank.process(&[m.release_escrow(1)], &[&m.merchant]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::InvalidState));
+    let err = m.bank.process(&[m.refund_escrow(1, m.buyer)], &[&m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::InvalidState));
+}
+
+#[test]
+fn the_arbiter_splits_the_escrow_and_the_fee_only_hits_the_merchant_share() {
+    let Setup { mut m, arbiter } = setup();
+    let escrow = m.open_escrow(1, AMOUNT);
+    let dispute = m.dispute_address(1);
+    m.bank.process(&[m.raise_dispute(1, m.buyer)], &[&m.buyer]).unwrap();
+
+    let impostor = Pubkey::new_unique();
+    let err = m.bank.process(&[resolve(&m, 1, impostor, 2_500)], &[&impostor]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::Unauthorized));
+
+    let fee_bps = m.bank.anchor_account::<Config>(&m.config).fee_bps as u64;
+    let rent = m.bank.lamports(&escrow) - AMOUNT;
+    let buyer_before = m.bank.lamports(&m.buyer);
+    let payout_before = m.bank.lamports(&m.payout_wallet);
+    m.bank.process(&[resolve(&m, 1, arbiter, 2_500)], &[&arbiter]).unwrap();
+
+    let to_buyer = AMOUNT / 4;
+    let fee = (AMOUNT - to_buyer) * fee_bps / 10_000;
+    assert!(m.bank.account(&escrow).is_none());
+    // The escrow closes to the buyer, so its rent comes back with their share
+    assert_eq!(m.bank.lamports(&m.buyer), buyer_before + to_buyer + rent);
+    assert_eq!(m.bank.lamports(&m.payout_wallet), payout_before + AMOUNT - to_buyer - fee);
+    assert_eq!(m.bank.anchor_account::<Treasury>(&m.treasury).total_fees, fee);
+
+    let record = m.bank.anchor_account::<Dispute>(&dispute);
+    assert_eq!((record.arbiter, record.buyer_bps, record.resolved_at), (arbiter, 2_500, m.bank.now()));
+}
+
+#[test]
+fn a_recreated_escrow_can_be_disputed_again() {
+    let Setup { mut m, arbiter } = setup();
+    m.open_escrow(1, AMOUNT);
+    let first = m.dispute_address(1);
+    m.bank.process(&[m.raise_dispute(1, m.buyer)], &[&m.buyer]).unwrap();
+    m.bank.process(&[resolve(&m, 1, arbiter, 10_000)], &[&arbiter]).unwrap();
+
+    // Same buyer and order_id, so the same escrow PDA, opened again later
+    m.bank.warp_to(m.bank.now() + DAY);
+    m.open_escrow(1, AMOUNT);
+    let second = m.dispute_address(1);
+    assert_ne!(first, second);
+    m.bank.process(&[m.raise_dispute(1, m.buyer)], &[&m.buyer]).unwrap();
+
+    // The first decision is still on record
+    let record = m.bank.anchor_account::<Dispute>(&first);
+    assert_eq!((record.buyer_bps, record.resolved_at), (10_000, m.bank.now() - DAY));
+    assert_eq!(m.bank.anchor_account::<Dispute>(&second).resolved_at, 0);
+}
+
+#[test]
+fn cancelling_a_disputed_escrow_closes_its_dispute() {
+    let Setup { mut m, .. } = setup();
+    let escrow = m.open_escrow(1, AMOUNT);
+    let dispute = m.dispute_address(1);
+    m.bank.process(&[m.raise_dispute(1, m.buyer)], &[&m.buyer]).unwrap();
+
+    let err = m.bank.process(&[m.mutual_cancel(1, None)], &[&m.buyer, &m.merchant]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::DisputeMissing));
+
+    m.bank.warp_to(m.bank.now() + 60);
+    m.bank.process(&[m.mutual_cancel(1, Some(dispute))], &[&m.buyer, &m.merchant]).unwrap();
+    assert!(m.bank.account(&escrow).is_none());
+    let record = m.bank.anchor_account::<Dispute>(&dispute);
+    assert_eq!((record.arbiter, record.buyer_bps, record.resolved_at), (Pubkey::default(), 10_000, m.bank.now()));
+}
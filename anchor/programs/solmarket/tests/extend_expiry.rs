@@ -0,0 +1,74 @@
+use anchor_lang::prelude::{ProgramError, Pubkey};
+use anchor_lang::solana_program::{instruction::Instruction, native_token::LAMPORTS_PER_SOL};
+use solmarket::handlers::EscrowError;
+use solmarket::state::{Config, SolEscrow};
+use solmarket_test_runtime::market::{Market, DAY};
+use solmarket_test_runtime::{event_authority, instruction, program_error};
+
+const AMOUNT: u64 = LAMPORTS_PER_SOL;
+
+fn extend(m: &Market, order_id: u64, merchant: Option<Pubkey>, new_expires_at: i64) -> Instruction {
+    instruction(
+        solmarket::accounts::ExtendExpiry {
+            buyer: m.buyer,
+            merchant,
+            config: m.config,
+            escrow: m.escrow_address(order_id),
+            event_authority: event_authority(),
+            program: solmarket::ID,
+        },
+        solmarket::instruction::ExtendExpiry { new_expires_at },
+    )
+}
+
+fn expires_at(m: &Market, escrow: &Pubkey) -> i64 {
+    m.bank.anchor_account::<SolEscrow>(escrow).expires_at
+}
+
+#[test]
+fn the_buyer_extends_and_the_merchant_can_co_sign() {
+    let mut m = Market::new();
+    let escrow = m.open_escrow(1, AMOUNT);
+    let first = expires_at(&m, &escrow) + DAY;
+    m.bank.process(&[extend(&m, 1, None, first)], &[&m.buyer]).unwrap();
+    assert_eq!(expires_at(&m, &escrow), first);
+
+    // A co-signer has to be the escrow's merchant
+    let stranger = Pubkey::new_unique();
+    let err = m.bank.process(&[extend(&m, 1, Some(stranger), first + DAY)], &[&m.buyer, &stranger]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::Unauthorized));
+    m.bank.process(&[extend(&m, 1, Some(m.merchant), first + DAY)], &[&m.buyer, &m.merchant]).unwrap();
+    assert_eq!(expires_at(&m, &escrow), first + DAY);
+
+    // The merchant cannot push the deadline out on their own
+    let err = m.bank.process(&[extend(&m, 1, Some(m.merchant), first + 2 * DAY)], &[&m.merchant]).unwrap_err();
+    assert_eq!(err, ProgramError::MissingRequiredSignature);
+}
+
+#[test]
+fn extensions_only_move_forward_and_stay_within_the_duration_cap() {
+    let mut m = Market::new();
+    let escrow = m.open_escrow(1, AMOUNT);
+    let current = expires_at(&m, &escrow);
+    let err = m.bank.process(&[extend(&m, 1, None, current)], &[&m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::InvalidExpiry));
+
+    // The cap counts from creation, so repeated extensions cannot stack past it
+    let latest = {
+        let created_at = m.bank.anchor_account::<SolEscrow>(&escrow).created_at;
+        created_at + m.bank.anchor_account::<Config>(&m.config).max_escrow_duration_secs
+    };
+    let err = m.bank.process(&[extend(&m, 1, None, latest + 1)], &[&m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::DurationTooLong));
+    m.bank.process(&[extend(&m, 1, None, latest)], &[&m.buyer]).unwrap();
+    assert_eq!(expires_at(&m, &escrow), latest);
+}
+
+#[test]
+fn a_disputed_escrow_cannot_be_extended() {
+    let mut m = Market::new();
+    let escrow = m.open_escrow(1, AMOUNT);
+    m.bank.process(&[m.raise_dispute(1, m.buyer)], &[&m.buyer]).unwrap();
+    let err = m.bank.process(&[extend(&m, 1, None, expires_at(&m, &escrow) + DAY)], &[&m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::InvalidState));
+}
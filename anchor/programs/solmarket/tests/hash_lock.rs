@@ -0,0 +1,78 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::{hash::hash, native_token::LAMPORTS_PER_SOL};
+use solmarket::handlers::EscrowError;
+use solmarket::state::{EscrowStatus, SolEscrow};
+use solmarket_test_runtime::market::{Market, DAY};
+use solmarket_test_runtime::{instruction, program_error};
+
+const AMOUNT: u64 = LAMPORTS_PER_SOL;
+const SECRET: &[u8] = b"locker 12, code 4417";
+
+// A funded escrow that only the merchant revealing SECRET releases, expiring in a day
+fn open_locked(m: &mut Market, order_id: u64) -> Pubkey {
+    let ix = instruction(
+        m.initialize_accounts(order_id),
+        solmarket::instruction::InitializeEscrow {
+            hash_lock: hash(SECRET).to_bytes(),
+            ..m.escrow_args(order_id, AMOUNT, m.bank.now() + DAY)
+        },
+    );
+    m.bank.process(&[ix, m.fund_escrow(order_id)], &[&m.buyer]).unwrap();
+    m.escrow_address(order_id)
+}
+
+#[test]
+fn a_hash_locked_escrow_cannot_be_marked_shipped() {
+    let mut m = Market::new();
+    let escrow = open_locked(&mut m, 1);
+    let err = m.bank.process(&[m.mark_shipped(1)], &[&m.merchant]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::InvalidState));
+    assert_eq!(m.bank.anchor_account::<SolEscrow>(&escrow).status, EscrowStatus::Funded as u8);
+
+    // Revealing the secret is still how the merchant gets paid
+    let release = instruction(
+        m.release_accounts(1, m.merchant),
+        solmarket::instruction::ReleaseWithSecret { secret: SECRET.to_vec() },
+    );
+    m.bank.process(&[release], &[&m.merchant]).unwrap();
+    assert!(m.bank.account(&escrow).is_none());
+}
+
+#[test]
+fn an_unrevealed_lock_refunds_the_buyer_at_expiry() {
+    let mut m = Market::new();
+    let escrow = open_locked(&mut m, 1);
+    let expires_at = m.bank.anchor_account::<SolEscrow>(&escrow).expires_at;
+    let err = m.bank.process(&[m.refund_escrow(1, m.buyer)], &[&m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::Unauthorized));
+
+    m.bank.warp_to(expires_at);
+    let before = m.bank.lamports(&m.buyer);
+    let held = m.bank.lamports(&escrow);
+    m.bank.process(&[m.refund_escrow(1, m.buyer)], &[&m.buyer]).unwrap();
+    assert!(m.bank.account(&escrow).is_none());
+    assert_eq!(m.bank.lamports(&m.buyer), before + held);
+}
+
+#[test]
+fn a_locked_escrow_already_marked_shipped_still_refunds_at_expiry() {
+    let mut m = Market::new();
+    let escrow = open_locked(&mut m, 1);
+    // Shipped before mark_shipped started refusing hash-locked escrows
+    let mut shipped = m.bank.anchor_account::<SolEscrow>(&escrow);
+    shipped.status = EscrowStatus::Shipped as u8;
+    shipped.shipped_at = m.bank.now();
+    let space = m.bank.account(&escrow).unwrap().data.len();
+    let held = m.bank.lamports(&escrow);
+    m.bank.set_anchor_account(escrow, &shipped, space);
+    // set_anchor_account leaves only rent behind; put the order amount back
+    m.bank.airdrop(&escrow, held - m.bank.lamports(&escrow));
+
+    m.bank.warp_to(shipped.expires_at);
+    let stranger = Pubkey::new_unique();
+    m.bank.airdrop(&stranger, LAMPORTS_PER_SOL);
+    let before = m.bank.lamports(&m.buyer);
+    m.bank.process(&[m.refund_escrow(1, stranger)], &[&stranger]).unwrap();
+    assert!(m.bank.account(&escrow).is_none());
+    assert_eq!(m.bank.lamports(&m.buyer), before + held);
+}
@@ -0,0 +1,38 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::{instruction::Instruction, native_token::LAMPORTS_PER_SOL};
+use solmarket::handlers::EscrowError;
+use solmarket::state::Merchant;
+use solmarket_test_runtime::market::{Market, DAY};
+use solmarket_test_runtime::{instruction, program_error};
+
+const AMOUNT: u64 = LAMPORTS_PER_SOL;
+
+fn set_verified(m: &Market, signer: Pubkey, verified: bool) -> Instruction {
+    instruction(
+        solmarket::accounts::UpdateMerchant { authority: signer, config: m.config, merchant: m.merchant_account },
+        solmarket::instruction::SetMerchantVerified { verified },
+    )
+}
+
+#[test]
+fn only_verified_merchants_take_new_orders_once_required() {
+    let mut m = Market::new();
+    // Unverified merchants take orders while the requirement is off
+    assert!(!m.bank.anchor_account::<Merchant>(&m.merchant_account).verified);
+    let open = m.open_escrow(1, AMOUNT);
+    m.configure(solmarket::instruction::SetRequireVerifiedMerchants { require_verified_merchants: true });
+    let err = m.bank.process(&[m.initialize_escrow(2, AMOUNT, m.bank.now() + DAY)], &[&m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::MerchantNotVerified));
+
+    let err = m.bank.process(&[set_verified(&m, m.merchant, true)], &[&m.merchant]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::Unauthorized));
+    m.bank.process(&[set_verified(&m, m.authority, true)], &[&m.authority]).unwrap();
+    m.open_escrow(2, AMOUNT);
+
+    // Losing verification stops new orders, not the ones already open
+    m.bank.process(&[set_verified(&m, m.authority, false)], &[&m.authority]).unwrap();
+    let err = m.bank.process(&[m.initialize_escrow(3, AMOUNT, m.bank.now() + DAY)], &[&m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::MerchantNotVerified));
+    m.bank.process(&[m.release_escrow(1)], &[&m.merchant]).unwrap();
+    assert!(m.bank.account(&open).is_none());
+}
@@ -0,0 +1,229 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::{instruction::Instruction, native_token::LAMPORTS_PER_SOL, system_program};
+use anchor_lang::{Discriminator, Space};
+use solmarket::handlers::{
+    EscrowError, BOND_WITHDRAW_DELAY_DEFAULT, CRANK_BOUNTY_DEFAULT, MAX_ESCROW_DURATION_DEFAULT, REVIEW_PERIOD_DEFAULT,
+};
+use solmarket::state::{Config, EscrowStatus, SolEscrow, CONFIG_VERSION, SOL_ESCROW_VERSION};
+use solmarket_test_runtime::{instruction, pda, program_error, rent_exempt, TestAccount, TestBank};
+
+const CONFIG_V0_LEN: usize = 75;
+const SOL_ESCROW_V0_LEN: usize = 108;
+
+// Store an account exactly as an older program version left it: that many bytes, rent-exempt at that size
+fn set_old_layout(bank: &mut TestBank, key: Pubkey, data: Vec<u8>) {
+    bank.set_account(
+        key,
+        TestAccount { lamports: rent_exempt(data.len()), data, owner: solmarket::ID, executable: false },
+    );
+}
+
+// Config v0: authority, fee_bps, fee_recipient, bump
+fn config_v0(authority: &Pubkey, fee_bps: u16, fee_recipient: &Pubkey, bump: u8) -> Vec<u8> {
+    let mut data = Config::DISCRIMINATOR.to_vec();
+    data.extend_from_slice(authority.as_ref());
+    data.extend_from_slice(&fee_bps.to_le_bytes());
+    data.extend_from_slice(fee_recipient.as_ref());
+    data.push(bump);
+    assert_eq!(data.len(), CONFIG_V0_LEN);
+    data
+}
+
+struct EscrowV0 {
+    order_id: u64,
+    buyer: Pubkey,
+    merchant: Pubkey,
+    amount_lamports: u64,
+    fee_bps: u16,
+    status: u8,
+    bump: u8,
+    created_at: i64,
+    expires_at: i64,
+}
+
+fn sol_escrow_v0(escrow: &EscrowV0) -> Vec<u8> {
+    let mut data = SolEscrow::DISCRIMINATOR.to_vec();
+    data.extend_from_slice(&escrow.order_id.to_le_bytes());
+    data.extend_from_slice(escrow.buyer.as_ref());
+    data.extend_from_slice(escrow.merchant.as_ref());
+    data.extend_from_slice(&escrow.amount_lamports.to_le_bytes());
+    data.extend_from_slice(&escrow.fee_bps.to_le_bytes());
+    data.push(escrow.status);
+    data.push(escrow.bump);
+    data.extend_from_slice(&escrow.created_at.to_le_bytes());
+    data.extend_from_slice(&escrow.expires_at.to_le_bytes());
+    assert_eq!(data.len(), SOL_ESCROW_V0_LEN);
+    data
+}
+
+fn migrate_config(config: Pubkey, authority: Pubkey) -> Instruction {
+    instruction(
+        solmarket::accounts::MigrateConfig { authority, system_program: system_program::ID, config },
+        solmarket::instruction::MigrateConfig {},
+    )
+}
+
+fn migrate_escrow(escrow: Pubkey, payer: Pubkey) -> Instruction {
+    instruction(
+        solmarket::accounts::MigrateEscrow { payer, system_program: system_program::ID, escrow },
+        solmarket::instruction::MigrateEscrow {},
+    )
+}
+
+#[test]
+fn a_v0_config_upgrades_with_defaults() {
+    let mut bank = TestBank::new();
+    let authority = Pubkey::new_unique();
+    let fee_recipient = Pubkey::new_unique();
+    bank.airdrop(&authority, LAMPORTS_PER_SOL);
+    let (config, bump) = Pubkey::find_program_address(&[b"config"], &solmarket::ID);
+    set_old_layout(&mut bank, config, config_v0(&authority, 350, &fee_recipient, bump));
+
+    // Only the stored authority may migrate
+    let stranger = Pubkey::new_unique();
+    bank.airdrop(&stranger, LAMPORTS_PER_SOL);
+    let err = bank.process(&[migrate_config(config, stranger)], &[&stranger]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::Unauthorized));
+
+    let authority_before = bank.lamports(&authority);
+    bank.process(&[migrate_config(config, authority)], &[&authority]).unwrap();
+
+    let full_len = Config::DISCRIMINATOR.len() + Config::INIT_SPACE;
+    let account = bank.account(&config).unwrap();
+    assert_eq!(account.data.len(), full_len);
+    assert_eq!(account.lamports, rent_exempt(full_len));
+    assert_eq!(authority_before - bank.lamports(&authority), rent_exempt(full_len) - rent_exempt(CONFIG_V0_LEN));
+
+    let upgraded = bank.anchor_account::<Config>(&config);
+    // Carried over from v0
+    assert_eq!(upgraded.authority, authority);
+    assert_eq!(upgraded.fee_bps, 350);
+    assert_eq!(upgraded.fee_recipient, fee_recipient);
+    assert_eq!(upgraded.bump, bump);
+    // Fields whose zero value would be wrong
+    assert_eq!(upgraded.arbiter, authority);
+    assert_eq!(upgraded.review_period_secs, REVIEW_PERIOD_DEFAULT);
+    assert_eq!(upgraded.max_escrow_duration_secs, MAX_ESCROW_DURATION_DEFAULT);
+    assert_eq!(upgraded.bond_withdraw_delay_secs, BOND_WITHDRAW_DELAY_DEFAULT);
+    assert_eq!(upgraded.crank_bounty_lamports, CRANK_BOUNTY_DEFAULT);
+    // Everything else starts switched off
+    assert_eq!(upgraded.pending_authority, Pubkey::default());
+    assert_eq!(upgraded.expiry_grace_secs, 0);
+    assert_eq!(upgraded.paused, 0);
+    assert_eq!(upgraded.order_signer, Pubkey::default());
+    assert_eq!(upgraded.fee_split_count, 0);
+    assert_eq!(upgraded.attester, Pubkey::default());
+    assert!(!upgraded.has_buyer_caps());
+    assert_eq!(upgraded.version, CONFIG_VERSION);
+
+    // A second run leaves a current config alone
+    let snapshot = bank.account(&config).cloned();
+    bank.process(&[migrate_config(config, authority)], &[&authority]).unwrap();
+    assert_eq!(bank.account(&config).cloned(), snapshot);
+}
+
+#[test]
+fn a_migrated_config_keeps_settings_changed_after_v0() {
+    let mut bank = TestBank::new();
+    let authority = Pubkey::new_unique();
+    bank.airdrop(&authority, LAMPORTS_PER_SOL);
+    let (config, bump) = Pubkey::find_program_address(&[b"config"], &solmarket::ID);
+    set_old_layout(&mut bank, config, config_v0(&authority, 200, &authority, bump));
+    bank.process(&[migrate_config(config, authority)], &[&authority]).unwrap();
+
+    // Once current, migrating again must not reset what the authority has since changed
+    let arbiter = Pubkey::new_unique();
+    let ix = instruction(
+        solmarket::accounts::UpdateConfig { authority, config },
+        solmarket::instruction::SetArbiter { arbiter },
+    );
+    bank.process(&[ix], &[&authority]).unwrap();
+    bank.process(&[migrate_config(config, authority)], &[&authority]).unwrap();
+    assert_eq!(bank.anchor_account::<Config>(&config).arbiter, arbiter);
+}
+
+#[test]
+fn a_v0_sol_escrow_upgrades_with_the_buyer_as_payer() {
+    let mut bank = TestBank::new();
+    let buyer = Pubkey::new_unique();
+    let order_id = 42u64;
+    let (escrow, bump) =
+        Pubkey::find_program_address(&[b"sol-escrow", buyer.as_ref(), &order_id.to_le_bytes()], &solmarket::ID);
+    let old = EscrowV0 {
+        order_id,
+        buyer,
+        merchant: Pubkey::new_unique(),
+        amount_lamports: 5 * LAMPORTS_PER_SOL,
+        fee_bps: 250,
+        status: EscrowStatus::Funded as u8,
+        bump,
+        created_at: 1_600_000_000,
+        expires_at: 1_600_086_400,
+    };
+    set_old_layout(&mut bank, escrow, sol_escrow_v0(&old));
+    // A funded v0 escrow holds its amount on top of rent
+    bank.airdrop(&escrow, old.amount_lamports);
+
+    // Anyone may migrate and fund the extra rent
+    let payer = Pubkey::new_unique();
+    bank.airdrop(&payer, LAMPORTS_PER_SOL);
+    bank.process(&[migrate_escrow(escrow, payer)], &[&payer]).unwrap();
+
+    let full_len = SolEscrow::DISCRIMINATOR.len() + SolEscrow::INIT_SPACE;
+    let account = bank.account(&escrow).unwrap();
+    assert_eq!(account.data.len(), full_len);
+    assert_eq!(account.lamports, rent_exempt(full_len) + old.amount_lamports);
+
+    let upgraded = bank.anchor_account::<SolEscrow>(&escrow);
+    assert_eq!(upgraded.order_id, old.order_id);
+    assert_eq!(upgraded.buyer, old.buyer);
+    assert_eq!(upgraded.merchant, old.merchant);
+    assert_eq!(upgraded.amount_lamports, old.amount_lamports);
+    assert_eq!(upgraded.fee_bps, old.fee_bps);
+    assert_eq!(upgraded.status, old.status);
+    assert_eq!(upgraded.bump, old.bump);
+    assert_eq!(upgraded.created_at, old.created_at);
+    assert_eq!(upgraded.expires_at, old.expires_at);
+    assert_eq!(upgraded.payer, buyer);
+    assert_eq!(upgraded.version, SOL_ESCROW_VERSION);
+    // A single full release, nothing paid, not shipped, hash-locked, referred or tracked
+    assert_eq!(upgraded.milestone_count, 0);
+    assert_eq!(upgraded.remaining_lamports(), old.amount_lamports);
+    assert_eq!(upgraded.refundable_lamports(), old.amount_lamports);
+    assert_eq!(upgraded.shipped_at, 0);
+    assert!(!upgraded.is_hash_locked());
+    assert_eq!(upgraded.referrer, Pubkey::default());
+    assert!(!upgraded.tracked_in_buyer_stats);
+
+    // No-op once current
+    let snapshot = bank.account(&escrow).cloned();
+    bank.process(&[migrate_escrow(escrow, payer)], &[&payer]).unwrap();
+    assert_eq!(bank.account(&escrow).cloned(), snapshot);
+}
+
+#[test]
+fn migrate_escrow_rejects_accounts_off_their_seeds() {
+    let mut bank = TestBank::new();
+    let buyer = Pubkey::new_unique();
+    let (_, bump) = Pubkey::find_program_address(&[b"sol-escrow", buyer.as_ref(), &7u64.to_le_bytes()], &solmarket::ID);
+    let old = EscrowV0 {
+        order_id: 7,
+        buyer,
+        merchant: Pubkey::new_unique(),
+        amount_lamports: LAMPORTS_PER_SOL,
+        fee_bps: 200,
+        status: EscrowStatus::Pending as u8,
+        bump,
+        created_at: 0,
+        expires_at: 1,
+    };
+    // Same bytes, stored at the PDA of a different order
+    let elsewhere = pda(&[b"sol-escrow", buyer.as_ref(), &8u64.to_le_bytes()]);
+    set_old_layout(&mut bank, elsewhere, sol_escrow_v0(&old));
+    let payer = Pubkey::new_unique();
+    bank.airdrop(&payer, LAMPORTS_PER_SOL);
+
+    let err = bank.process(&[migrate_escrow(elsewhere, payer)], &[&payer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::InvalidState));
+    assert_eq!(bank.account(&elsewhere).unwrap().data.len(), SOL_ESCROW_V0_LEN);
+}
@@ -0,0 +1,137 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::{instruction::Instruction, program_error::ProgramError, system_program, sysvar};
+use anchor_lang::{Discriminator, Space};
+use solana_sdk::signature::{Keypair, Signer};
+use solmarket::handlers::{order_intent_message, EscrowError};
+use solmarket::state::OrderIntent;
+use solmarket_test_runtime::market::{Market, DAY};
+use solmarket_test_runtime::{ed25519_verify, event_authority, instruction, pda, program_error, rent_exempt};
+
+const NONCE: u64 = 9;
+
+struct Setup {
+    m: Market,
+    order_signer: Keypair,
+    expires_at: i64,
+}
+
+fn setup() -> Setup {
+    let mut m = Market::new();
+    let order_signer = Keypair::new();
+    let ix = instruction(
+        solmarket::accounts::UpdateConfig { authority: m.authority, config: m.config },
+        solmarket::instruction::SetOrderSigner { order_signer: order_signer.pubkey() },
+    );
+    m.bank.process(&[ix], &[&m.authority]).unwrap();
+    let expires_at = m.bank.now() + DAY;
+    Setup { m, order_signer, expires_at }
+}
+
+fn intent_address(buyer: &Pubkey, nonce: u64) -> Pubkey {
+    pda(&[b"order-intent", buyer.as_ref(), &nonce.to_le_bytes()])
+}
+
+// The signed intent followed by the initialize_escrow it authorizes
+fn signed_order(s: &Setup, order_id: u64, with_intent: bool) -> [Instruction; 2] {
+    let m = &s.m;
+    let amount = 1_000_000;
+    let message = order_intent_message(order_id, &m.buyer, &m.merchant, amount, s.expires_at, NONCE);
+    let accounts = solmarket::accounts::InitializeEscrow {
+        instructions: Some(sysvar::instructions::ID),
+        intent: with_intent.then(|| intent_address(&m.buyer, NONCE)),
+        ..m.initialize_accounts(order_id)
+    };
+    let init = instruction(
+        accounts,
+        solmarket::instruction::InitializeEscrow {
+            order_id,
+            amount_lamports: amount,
+            expires_at: s.expires_at,
+            milestones: vec![],
+            intent_nonce: NONCE,
+            hash_lock: [0u8; 32],
+        },
+    );
+    [ed25519_verify(&s.order_signer, &message), init]
+}
+
+fn refund_escrow(m: &Market, order_id: u64) -> Instruction {
+    instruction(
+        solmarket::accounts::RefundEscrow {
+            authority: m.buyer,
+            system_program: system_program::ID,
+            config: m.config,
+            buyer_account: m.buyer,
+            buyer_stats: None,
+            escrow: m.escrow_address(order_id),
+            event_authority: event_authority(),
+            program: solmarket::ID,
+        },
+        solmarket::instruction::RefundEscrow {},
+    )
+}
+
+fn close_order_intent(m: &Market, payer: Pubkey) -> Instruction {
+    instruction(
+        solmarket::accounts::CloseOrderIntent { payer, intent: intent_address(&m.buyer, NONCE) },
+        solmarket::instruction::CloseOrderIntent {},
+    )
+}
+
+#[test]
+fn an_intent_opens_a_single_escrow() {
+    let mut s = setup();
+    let ixs = signed_order(&s, 1, true);
+    s.m.bank.process(&ixs, &[&s.m.buyer]).unwrap();
+
+    let intent = s.m.bank.anchor_account::<OrderIntent>(&intent_address(&s.m.buyer, NONCE));
+    assert_eq!(intent.buyer, s.m.buyer);
+    assert_eq!(intent.nonce, NONCE);
+    assert_eq!(intent.payer, s.m.buyer);
+    assert_eq!(intent.expires_at, s.expires_at);
+
+    // Cancelling the order frees its escrow address, but not the intent
+    let ix = refund_escrow(&s.m, 1);
+    s.m.bank.process(&[ix], &[&s.m.buyer]).unwrap();
+    assert!(s.m.bank.account(&s.m.escrow_address(1)).is_none());
+
+    // Replaying it fails to create the nonce record again (SystemError::AccountAlreadyInUse)
+    let ixs = signed_order(&s, 1, true);
+    let err = s.m.bank.process(&ixs, &[&s.m.buyer]).unwrap_err();
+    assert_eq!(err, ProgramError::Custom(0));
+    assert!(s.m.bank.account(&s.m.escrow_address(1)).is_none());
+}
+
+#[test]
+fn an_intent_needs_its_nonce_account() {
+    let mut s = setup();
+    let ixs = signed_order(&s, 1, false);
+    let err = s.m.bank.process(&ixs, &[&s.m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::IntentNonceMissing));
+}
+
+#[test]
+fn a_used_intent_closes_once_expired() {
+    let mut s = setup();
+    let ixs = signed_order(&s, 1, true);
+    s.m.bank.process(&ixs, &[&s.m.buyer]).unwrap();
+    let intent = intent_address(&s.m.buyer, NONCE);
+
+    // No signature needed, but only once the intent could no longer open an escrow
+    let ix = close_order_intent(&s.m, s.m.buyer);
+    let err = s.m.bank.process(&[ix], &[]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::NotExpired));
+
+    s.m.bank.warp_to(s.expires_at);
+    let before = s.m.bank.lamports(&s.m.buyer);
+    let ix = close_order_intent(&s.m, s.m.buyer);
+    s.m.bank.process(&[ix], &[]).unwrap();
+    assert!(s.m.bank.account(&intent).is_none());
+    let space = OrderIntent::DISCRIMINATOR.len() + OrderIntent::INIT_SPACE;
+    assert_eq!(s.m.bank.lamports(&s.m.buyer), before + rent_exempt(space));
+
+    // And from then on the intent is stale
+    let ixs = signed_order(&s, 2, true);
+    let err = s.m.bank.process(&ixs, &[&s.m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::InvalidExpiry));
+}
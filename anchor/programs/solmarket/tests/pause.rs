@@ -0,0 +1,84 @@
+use anchor_lang::solana_program::{instruction::Instruction, native_token::LAMPORTS_PER_SOL, system_program};
+use solmarket::handlers::EscrowError;
+use solmarket::state::{Config, SolEscrow, PAUSE_CREATE, PAUSE_FUND, PAUSE_REFUND, PAUSE_RELEASE};
+use solmarket_test_runtime::market::{Market, DAY};
+use solmarket_test_runtime::{event_authority, instruction, program_error};
+
+const AMOUNT: u64 = LAMPORTS_PER_SOL;
+
+fn expire(m: &Market, order_id: u64) -> Instruction {
+    instruction(
+        solmarket::accounts::ExpireEscrow {
+            system_program: system_program::ID,
+            config: m.config,
+            buyer_account: m.buyer,
+            payer: m.buyer,
+            buyer_stats: None,
+            escrow: m.escrow_address(order_id),
+            event_authority: event_authority(),
+            program: solmarket::ID,
+        },
+        solmarket::instruction::ExpireEscrow {},
+    )
+}
+
+#[test]
+fn creating_and_funding_stop_under_their_own_bits() {
+    let mut m = Market::new();
+    let expires_at = m.bank.now() + DAY;
+    m.set_paused(PAUSE_CREATE);
+    let err = m.bank.process(&[m.initialize_escrow(1, AMOUNT, expires_at)], &[&m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::Paused));
+
+    m.set_paused(PAUSE_FUND);
+    m.bank.process(&[m.initialize_escrow(1, AMOUNT, expires_at)], &[&m.buyer]).unwrap();
+    let err = m.bank.process(&[m.fund_escrow(1)], &[&m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::Paused));
+
+    m.set_paused(PAUSE_CREATE | PAUSE_RELEASE | PAUSE_REFUND);
+    m.bank.process(&[m.fund_escrow(1)], &[&m.buyer]).unwrap();
+}
+
+#[test]
+fn releases_stop_under_pause_release() {
+    let mut m = Market::new();
+    let escrow = m.open_escrow(1, AMOUNT);
+    m.set_paused(PAUSE_RELEASE);
+    let err = m.bank.process(&[m.release_escrow(1)], &[&m.merchant]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::Paused));
+    // Not even the platform authority gets around it
+    let release = instruction(m.release_accounts(1, m.authority), solmarket::instruction::ReleaseEscrow {});
+    let err = m.bank.process(&[release], &[&m.authority]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::Paused));
+
+    m.set_paused(PAUSE_CREATE | PAUSE_FUND | PAUSE_REFUND);
+    m.bank.process(&[m.release_escrow(1)], &[&m.merchant]).unwrap();
+    assert!(m.bank.account(&escrow).is_none());
+}
+
+#[test]
+fn buyers_still_refund_with_refunds_paused() {
+    let mut m = Market::new();
+    let first = m.open_escrow(1, AMOUNT);
+    let second = m.open_escrow(2, AMOUNT);
+    m.set_paused(PAUSE_REFUND);
+
+    // The authority's refund and the permissionless expiry both wait
+    let err = m.bank.process(&[m.refund_escrow(1, m.authority)], &[&m.authority]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::Paused));
+    let grace_end = {
+        let escrow = m.bank.anchor_account::<SolEscrow>(&second);
+        escrow.expires_at + m.bank.anchor_account::<Config>(&m.config).expiry_grace_secs
+    };
+    m.bank.warp_to(grace_end);
+    let err = m.bank.process(&[expire(&m, 2)], &[]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::Paused));
+
+    // The buyer takes both back themselves
+    let before = m.bank.lamports(&m.buyer);
+    let held = m.bank.lamports(&first) + m.bank.lamports(&second);
+    let refunds = [m.refund_escrow(1, m.buyer), m.refund_escrow(2, m.buyer)];
+    m.bank.process(&refunds, &[&m.buyer]).unwrap();
+    assert!(m.bank.account(&first).is_none() && m.bank.account(&second).is_none());
+    assert_eq!(m.bank.lamports(&m.buyer), before + held);
+}
@@ -0,0 +1,127 @@
+use anchor_lang::prelude::{ProgramError, Pubkey};
+use anchor_lang::solana_program::{instruction::Instruction, native_token::LAMPORTS_PER_SOL, system_program};
+use solmarket::handlers::EscrowError;
+use solmarket::state::{Config, Referrer, SolEscrow, Treasury};
+use solmarket_test_runtime::market::{Market, DAY};
+use solmarket_test_runtime::{instruction, pda, program_error};
+
+// Small enough that the referral share is below a fresh wallet's rent-exempt minimum
+const AMOUNT: u64 = 10_000_000;
+
+// Referrals capped at 50% of the fee
+fn setup() -> Market {
+    let mut m = Market::new();
+    let cap = instruction(
+        solmarket::accounts::UpdateConfig { authority: m.authority, config: m.config },
+        solmarket::instruction::SetMaxReferralBps { max_referral_bps: 5_000 },
+    );
+    m.bank.process(&[cap], &[&m.authority]).unwrap();
+    m
+}
+
+fn register(m: &Market, signer: Pubkey, wallet: Pubkey, referral_bps: u16) -> Instruction {
+    instruction(
+        solmarket::accounts::RegisterReferrer {
+            authority: signer,
+            system_program: system_program::ID,
+            config: m.config,
+            wallet,
+            referrer: pda(&[b"referrer", wallet.as_ref()]),
+        },
+        solmarket::instruction::RegisterReferrer { referral_bps },
+    )
+}
+
+// Register `wallet` as a referrer, returning its Referrer account
+fn registered(m: &mut Market, wallet: Pubkey, referral_bps: u16) -> Pubkey {
+    m.bank.process(&[register(m, m.authority, wallet, referral_bps)], &[&m.authority]).unwrap();
+    pda(&[b"referrer", wallet.as_ref()])
+}
+
+fn open_referred(m: &mut Market, order_id: u64, referrer: Pubkey) -> Result<Pubkey, ProgramError> {
+    let ix = instruction(
+        solmarket::accounts::InitializeEscrow { referrer: Some(referrer), ..m.initialize_accounts(order_id) },
+        m.escrow_args(order_id, AMOUNT, m.bank.now() + DAY),
+    );
+    m.bank.process(&[ix, m.fund_escrow(order_id)], &[&m.buyer])?;
+    Ok(m.escrow_address(order_id))
+}
+
+fn claim(wallet: Pubkey) -> Instruction {
+    instruction(
+        solmarket::accounts::ClaimReferralFees { wallet, referrer: pda(&[b"referrer", wallet.as_ref()]) },
+        solmarket::instruction::ClaimReferralFees {},
+    )
+}
+
+#[test]
+fn only_the_authority_registers_referrers_within_the_cap() {
+    let mut m = setup();
+    let wallet = Pubkey::new_unique();
+    let err = m.bank.process(&[register(&m, m.buyer, wallet, 1_000)], &[&m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::Unauthorized));
+    let err = m.bank.process(&[register(&m, m.authority, wallet, 5_001)], &[&m.authority]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::ReferralTooHigh));
+
+    let referrer = registered(&mut m, wallet, 1_000);
+    let record = m.bank.anchor_account::<Referrer>(&referrer);
+    assert_eq!((record.wallet, record.referral_bps, record.total_earned), (wallet, 1_000, 0));
+}
+
+#[test]
+fn buyers_cannot_refer_their_own_orders() {
+    let mut m = setup();
+    let buyer = m.buyer;
+    let referrer = registered(&mut m, buyer, 5_000);
+    let err = open_referred(&mut m, 1, referrer).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::SelfReferral));
+    assert!(m.bank.account(&m.escrow_address(1)).is_none());
+}
+
+#[test]
+fn referral_fees_accrue_to_a_fresh_wallets_referrer_and_it_claims_them() {
+    let mut m = setup();
+    // Never funded, so paying it a small share directly would fail rent checks
+    let wallet = Pubkey::new_unique();
+    let referrer = registered(&mut m, wallet, 2_500);
+    let escrow = open_referred(&mut m, 1, referrer).unwrap();
+    assert_eq!(m.bank.anchor_account::<SolEscrow>(&escrow).referral_bps, 2_500);
+
+    // The share is fixed at creation, whatever the registration says later
+    let update = instruction(
+        solmarket::accounts::UpdateReferrer { authority: m.authority, config: m.config, referrer },
+        solmarket::instruction::SetReferralBps { referral_bps: 0 },
+    );
+    m.bank.process(&[update], &[&m.authority]).unwrap();
+
+    let err = m.bank.process(&[m.release_escrow(1)], &[&m.merchant]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::ReferrerMissing));
+
+    let fee = AMOUNT * m.bank.anchor_account::<Config>(&m.config).fee_bps as u64 / 10_000;
+    let referral_fee = fee / 4;
+    let held = m.bank.lamports(&referrer);
+    let release = instruction(
+        solmarket::accounts::ReleaseEscrow { referrer: Some(referrer), ..m.release_accounts(1, m.merchant) },
+        solmarket::instruction::ReleaseEscrow {},
+    );
+    m.bank.process(&[release], &[&m.merchant]).unwrap();
+    assert_eq!(m.bank.lamports(&referrer), held + referral_fee);
+    assert_eq!(m.bank.anchor_account::<Referrer>(&referrer).total_earned, referral_fee);
+    assert_eq!(m.bank.anchor_account::<Treasury>(&m.treasury).total_fees, fee - referral_fee);
+    assert_eq!(m.bank.lamports(&wallet), 0);
+
+    let stranger = Pubkey::new_unique();
+    m.bank.airdrop(&stranger, LAMPORTS_PER_SOL);
+    let mut steal = claim(wallet);
+    steal.accounts[0].pubkey = stranger;
+    assert!(m.bank.process(&[steal], &[&stranger]).is_err());
+
+    // Claiming is the wallet's own transaction, so by then it holds enough for fees
+    m.bank.airdrop(&wallet, LAMPORTS_PER_SOL);
+    m.bank.process(&[claim(wallet)], &[&wallet]).unwrap();
+    assert_eq!(m.bank.lamports(&wallet), LAMPORTS_PER_SOL + referral_fee);
+    assert_eq!(m.bank.lamports(&referrer), held);
+    assert_eq!(m.bank.anchor_account::<Referrer>(&referrer).total_claimed, referral_fee);
+    let err = m.bank.process(&[claim(wallet)], &[&wallet]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::InvalidAmount));
+}
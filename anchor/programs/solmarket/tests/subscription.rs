@@ -0,0 +1,121 @@
+use anchor_lang::solana_program::{instruction::Instruction, native_token::LAMPORTS_PER_SOL, system_program};
+use anchor_lang::prelude::Pubkey;
+use solmarket::handlers::EscrowError;
+use solmarket::state::{Config, Subscription, Treasury, PAUSE_RELEASE};
+use solmarket_test_runtime::market::{Market, DAY};
+use solmarket_test_runtime::{event_authority, instruction, pda, program_error};
+
+const PER_PERIOD: u64 = LAMPORTS_PER_SOL;
+
+// Daily subscription 1, starting now, with three periods prepaid
+fn subscribe(m: &mut Market) -> Pubkey {
+    let subscription = pda(&[b"subscription", m.buyer.as_ref(), &1u64.to_le_bytes()]);
+    let ix = instruction(
+        solmarket::accounts::CreateSubscription {
+            buyer: m.buyer,
+            system_program: system_program::ID,
+            config: m.config,
+            merchant: m.merchant_account,
+            subscription,
+            event_authority: event_authority(),
+            program: solmarket::ID,
+        },
+        solmarket::instruction::CreateSubscription {
+            subscription_id: 1,
+            period_secs: DAY,
+            amount_per_period: PER_PERIOD,
+            first_charge_at: m.bank.now(),
+            periods: 3,
+        },
+    );
+    m.bank.process(&[ix], &[&m.buyer]).unwrap();
+    subscription
+}
+
+fn charge(m: &Market, subscription: Pubkey) -> Instruction {
+    instruction(
+        solmarket::accounts::ChargePeriod {
+            config: m.config,
+            merchant_account: m.merchant_account,
+            payout_wallet: m.payout_wallet,
+            treasury: m.treasury,
+            subscription,
+            event_authority: event_authority(),
+            program: solmarket::ID,
+        },
+        solmarket::instruction::ChargePeriod {},
+    )
+}
+
+fn cancel(m: &Market, subscription: Pubkey) -> Instruction {
+    instruction(
+        solmarket::accounts::CancelSubscription {
+            buyer: m.buyer,
+            config: m.config,
+            merchant_account: m.merchant_account,
+            payout_wallet: m.payout_wallet,
+            treasury: m.treasury,
+            subscription,
+            event_authority: event_authority(),
+            program: solmarket::ID,
+        },
+        solmarket::instruction::CancelSubscription {},
+    )
+}
+
+#[test]
+fn cancelling_charges_started_periods_and_refunds_the_rest() {
+    let mut m = Market::new();
+    let subscription = subscribe(&mut m);
+
+    // Halfway through the second period, with nothing charged yet
+    m.bank.warp_to(m.bank.now() + DAY + DAY / 2);
+    let fee_bps = m.bank.anchor_account::<Config>(&m.config).fee_bps as u64;
+    let payout_before = m.bank.lamports(&m.payout_wallet);
+    let buyer_before = m.bank.lamports(&m.buyer);
+    let held = m.bank.lamports(&subscription);
+    m.bank.process(&[cancel(&m, subscription)], &[&m.buyer]).unwrap();
+
+    // Both started periods go to the merchant, only the third comes back
+    let fee = PER_PERIOD * fee_bps / 10_000;
+    assert!(m.bank.account(&subscription).is_none());
+    assert_eq!(m.bank.lamports(&m.payout_wallet), payout_before + 2 * (PER_PERIOD - fee));
+    assert_eq!(m.bank.lamports(&m.buyer), buyer_before + held - 2 * PER_PERIOD);
+    let treasury = m.bank.anchor_account::<Treasury>(&m.treasury);
+    assert_eq!(treasury.total_fees, 2 * fee);
+    assert_eq!(treasury.volume_lamports, 2 * PER_PERIOD);
+}
+
+#[test]
+fn cancelling_with_releases_paused_defers_started_periods() {
+    let mut m = Market::new();
+    let subscription = subscribe(&mut m);
+    m.bank.warp_to(m.bank.now() + DAY + DAY / 2);
+    m.set_paused(PAUSE_RELEASE);
+    let fee_bps = m.bank.anchor_account::<Config>(&m.config).fee_bps as u64;
+    let payout_before = m.bank.lamports(&m.payout_wallet);
+    let buyer_before = m.bank.lamports(&m.buyer);
+    let rent = m.bank.lamports(&subscription) - 3 * PER_PERIOD;
+
+    // The unstarted third period comes back now; the two started ones stay owed
+    m.bank.process(&[cancel(&m, subscription)], &[&m.buyer]).unwrap();
+    assert_eq!(m.bank.lamports(&m.buyer), buyer_before + PER_PERIOD);
+    assert_eq!(m.bank.anchor_account::<Subscription>(&subscription).prepaid_lamports, 2 * PER_PERIOD);
+    let err = m.bank.process(&[charge(&m, subscription)], &[&m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::Paused));
+
+    // Once unpaused the merchant collects them, and nothing more comes due
+    m.set_paused(0);
+    m.bank.warp_to(m.bank.now() + 5 * DAY);
+    let charges = [charge(&m, subscription), charge(&m, subscription)];
+    m.bank.process(&charges, &[&m.buyer]).unwrap();
+    let fee = PER_PERIOD * fee_bps / 10_000;
+    assert_eq!(m.bank.lamports(&m.payout_wallet), payout_before + 2 * (PER_PERIOD - fee));
+    let err = m.bank.process(&[charge(&m, subscription)], &[&m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::InvalidAmount));
+
+    // Cancelling again closes it and returns the rent
+    m.bank.process(&[cancel(&m, subscription)], &[&m.buyer]).unwrap();
+    assert!(m.bank.account(&subscription).is_none());
+    assert_eq!(m.bank.lamports(&m.buyer), buyer_before + PER_PERIOD + rent);
+}
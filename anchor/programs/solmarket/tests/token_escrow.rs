@@ -0,0 +1,235 @@
+use anchor_lang::error::ErrorCode;
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::{instruction::Instruction, native_token::LAMPORTS_PER_SOL, system_program};
+use anchor_spl::associated_token::{self, get_associated_token_address_with_program_id};
+use anchor_spl::token::spl_token;
+use anchor_spl::token_2022::spl_token_2022;
+use solmarket::handlers::EscrowError;
+use solmarket::state::{Config, EscrowStatus, TokenEscrow, PAUSE_REFUND};
+use solmarket_test_runtime::market::{Market, DAY};
+use solmarket_test_runtime::token::{create_mint, create_token_account, mint_to, token_balance};
+use solmarket_test_runtime::{instruction, pda, program_error};
+
+const AMOUNT: u64 = 5_000_000;
+
+struct Setup {
+    m: Market,
+    token_program: Pubkey,
+    mint: Pubkey,
+    buyer_tokens: Pubkey,
+}
+
+// A market with a mint under `token_program` and a buyer holding twice AMOUNT of it
+fn setup(token_program: Pubkey, transfer_fee_bps: Option<u16>) -> Setup {
+    let mut m = Market::new();
+    let mint = create_mint(&mut m.bank, &m.authority, &token_program, transfer_fee_bps);
+    let buyer_tokens = create_token_account(&mut m.bank, &m.buyer, &m.buyer, &mint, &token_program);
+    mint_to(&mut m.bank, &m.authority, &mint, &buyer_tokens, &token_program, 2 * AMOUNT);
+    Setup { m, token_program, mint, buyer_tokens }
+}
+
+impl Setup {
+    fn escrow(&self, order_id: u64) -> Pubkey {
+        pda(&[b"token-escrow", self.m.buyer.as_ref(), &order_id.to_le_bytes()])
+    }
+
+    fn tokens_of(&self, owner: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(owner, &self.mint, &self.token_program)
+    }
+
+    fn vault(&self, order_id: u64) -> Pubkey {
+        self.tokens_of(&self.escrow(order_id))
+    }
+
+    fn initialize(&self, order_id: u64) -> Instruction {
+        instruction(
+            solmarket::accounts::InitializeTokenEscrow {
+                associated_token_program: associated_token::ID,
+                token_program: self.token_program,
+                system_program: system_program::ID,
+                payer: self.m.buyer,
+                config: self.m.config,
+                buyer: self.m.buyer,
+                merchant: self.m.merchant_account,
+                mint: self.mint,
+                escrow: self.escrow(order_id),
+                vault: self.vault(order_id),
+            },
+            solmarket::instruction::InitializeTokenEscrow { order_id, amount: AMOUNT, expires_at: self.m.bank.now() + DAY },
+        )
+    }
+
+    fn fund(&self, order_id: u64) -> Instruction {
+        instruction(
+            solmarket::accounts::FundTokenEscrow {
+                token_program: self.token_program,
+                buyer: self.m.buyer,
+                config: self.m.config,
+                mint: self.mint,
+                buyer_token_account: self.buyer_tokens,
+                escrow: self.escrow(order_id),
+                vault: self.vault(order_id),
+            },
+            solmarket::instruction::FundTokenEscrow {},
+        )
+    }
+
+    fn open(&mut self, order_id: u64) -> Pubkey {
+        let ixs = [self.initialize(order_id), self.fund(order_id)];
+        self.m.bank.process(&ixs, &[&self.m.buyer]).unwrap();
+        self.escrow(order_id)
+    }
+
+    fn release(&self, order_id: u64, signer: Pubkey) -> Instruction {
+        instruction(
+            solmarket::accounts::ReleaseTokenEscrow {
+                associated_token_program: associated_token::ID,
+                token_program: self.token_program,
+                system_program: system_program::ID,
+                authority: signer,
+                config: self.m.config,
+                merchant_account: self.m.merchant_account,
+                payout_wallet: self.m.payout_wallet,
+                fee_recipient: self.m.authority,
+                mint: self.mint,
+                merchant_token_account: self.tokens_of(&self.m.payout_wallet),
+                fee_recipient_token_account: self.tokens_of(&self.m.authority),
+                escrow: self.escrow(order_id),
+                vault: self.vault(order_id),
+            },
+            solmarket::instruction::ReleaseTokenEscrow {},
+        )
+    }
+
+    fn refund_accounts(&self, order_id: u64, signer: Pubkey) -> solmarket::accounts::RefundTokenEscrow {
+        solmarket::accounts::RefundTokenEscrow {
+            associated_token_program: associated_token::ID,
+            token_program: self.token_program,
+            system_program: system_program::ID,
+            authority: signer,
+            config: self.m.config,
+            buyer_account: self.m.buyer,
+            mint: self.mint,
+            buyer_token_account: self.buyer_tokens,
+            escrow: self.escrow(order_id),
+            vault: self.vault(order_id),
+        }
+    }
+}
+
+#[test]
+fn a_token_escrow_releases_to_the_merchant_less_the_fee() {
+    let mut s = setup(spl_token::ID, None);
+    let escrow = s.open(1);
+    assert_eq!(token_balance(&s.m.bank, &s.vault(1)), AMOUNT);
+    assert_eq!(token_balance(&s.m.bank, &s.buyer_tokens), AMOUNT);
+    assert_eq!(s.m.bank.anchor_account::<TokenEscrow>(&escrow).status, EscrowStatus::Funded as u8);
+
+    let fee_bps = s.m.bank.anchor_account::<Config>(&s.m.config).fee_bps as u64;
+    s.m.bank.process(&[s.release(1, s.m.merchant)], &[&s.m.merchant]).unwrap();
+
+    let fee = AMOUNT * fee_bps / 10_000;
+    assert_eq!(token_balance(&s.m.bank, &s.tokens_of(&s.m.payout_wallet)), AMOUNT - fee);
+    assert_eq!(token_balance(&s.m.bank, &s.tokens_of(&s.m.authority)), fee);
+    assert!(s.m.bank.account(&escrow).is_none());
+    assert!(s.m.bank.account(&s.vault(1)).is_none());
+}
+
+#[test]
+fn a_token_2022_escrow_refunds_the_buyer() {
+    let mut s = setup(spl_token_2022::ID, None);
+    let escrow = s.open(1);
+    let stranger = Pubkey::new_unique();
+    s.m.bank.airdrop(&stranger, LAMPORTS_PER_SOL);
+    let ix = instruction(s.refund_accounts(1, stranger), solmarket::instruction::RefundTokenEscrow {});
+    let err = s.m.bank.process(&[ix], &[&stranger]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::Unauthorized));
+
+    let ix = instruction(s.refund_accounts(1, s.m.buyer), solmarket::instruction::RefundTokenEscrow {});
+    s.m.bank.process(&[ix], &[&s.m.buyer]).unwrap();
+    assert_eq!(token_balance(&s.m.bank, &s.buyer_tokens), 2 * AMOUNT);
+    assert!(s.m.bank.account(&escrow).is_none());
+    assert!(s.m.bank.account(&s.vault(1)).is_none());
+}
+
+#[test]
+fn anyone_expires_a_funded_token_escrow_after_the_grace_window() {
+    let mut s = setup(spl_token_2022::ID, None);
+    let escrow = s.open(1);
+    let grace_end = {
+        let escrow = s.m.bank.anchor_account::<TokenEscrow>(&escrow);
+        escrow.expires_at + s.m.bank.anchor_account::<Config>(&s.m.config).expiry_grace_secs
+    };
+    let cranker = Pubkey::new_unique();
+    s.m.bank.airdrop(&cranker, LAMPORTS_PER_SOL);
+    let expire = [instruction(s.refund_accounts(1, cranker), solmarket::instruction::ExpireTokenEscrow {})];
+
+    s.m.bank.warp_to(grace_end - 1);
+    let err = s.m.bank.process(&expire, &[&cranker]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::NotExpired));
+
+    s.m.bank.warp_to(grace_end);
+    s.m.bank.process(&expire, &[&cranker]).unwrap();
+    assert_eq!(token_balance(&s.m.bank, &s.buyer_tokens), 2 * AMOUNT);
+    assert!(s.m.bank.account(&escrow).is_none());
+}
+
+#[test]
+fn only_the_buyer_expires_a_token_escrow_while_refunds_are_paused() {
+    let mut s = setup(spl_token::ID, None);
+    let escrow = s.open(1);
+    let grace_end = {
+        let escrow = s.m.bank.anchor_account::<TokenEscrow>(&escrow);
+        escrow.expires_at + s.m.bank.anchor_account::<Config>(&s.m.config).expiry_grace_secs
+    };
+    s.m.bank.warp_to(grace_end);
+    s.m.set_paused(PAUSE_REFUND);
+    let cranker = Pubkey::new_unique();
+    s.m.bank.airdrop(&cranker, LAMPORTS_PER_SOL);
+    let ix = instruction(s.refund_accounts(1, cranker), solmarket::instruction::ExpireTokenEscrow {});
+    let err = s.m.bank.process(&[ix], &[&cranker]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::Paused));
+
+    let ix = instruction(s.refund_accounts(1, s.m.buyer), solmarket::instruction::ExpireTokenEscrow {});
+    s.m.bank.process(&[ix], &[&s.m.buyer]).unwrap();
+    assert_eq!(token_balance(&s.m.bank, &s.buyer_tokens), 2 * AMOUNT);
+    assert!(s.m.bank.account(&escrow).is_none());
+}
+
+#[test]
+fn a_vault_created_ahead_of_the_escrow_does_not_block_it() {
+    let mut s = setup(spl_token::ID, None);
+    // The vault is an ATA of a predictable PDA, so anyone can create it first
+    let stranger = Pubkey::new_unique();
+    s.m.bank.airdrop(&stranger, LAMPORTS_PER_SOL);
+    let (escrow, mint, token_program) = (s.escrow(1), s.mint, s.token_program);
+    let vault = create_token_account(&mut s.m.bank, &stranger, &escrow, &mint, &token_program);
+    assert_eq!(vault, s.vault(1));
+
+    s.open(1);
+    assert_eq!(token_balance(&s.m.bank, &vault), AMOUNT);
+    s.m.bank.process(&[s.release(1, s.m.merchant)], &[&s.m.merchant]).unwrap();
+    assert!(s.m.bank.account(&vault).is_none());
+}
+
+#[test]
+fn a_vault_of_another_mint_is_rejected() {
+    let mut s = setup(spl_token::ID, None);
+    let other = create_mint(&mut s.m.bank, &s.m.authority, &spl_token::ID, None);
+    let (escrow, buyer) = (s.escrow(1), s.m.buyer);
+    // The escrow's ATA, but for a different mint
+    let wrong_vault = create_token_account(&mut s.m.bank, &buyer, &escrow, &other, &spl_token::ID);
+    let mut ix = s.initialize(1);
+    ix.accounts.iter_mut().find(|meta| meta.pubkey == s.vault(1)).unwrap().pubkey = wrong_vault;
+    let err = s.m.bank.process(&[ix], &[&s.m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(ErrorCode::ConstraintTokenMint));
+    assert!(s.m.bank.account(&s.escrow(1)).is_none());
+}
+
+#[test]
+fn transfer_fee_mints_are_rejected() {
+    let mut s = setup(spl_token_2022::ID, Some(100));
+    let err = s.m.bank.process(&[s.initialize(1)], &[&s.m.buyer]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::TransferFeeMint));
+    assert!(s.m.bank.account(&s.escrow(1)).is_none());
+}
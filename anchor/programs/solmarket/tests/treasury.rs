@@ -0,0 +1,209 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::prelude::AccountMeta;
+use anchor_lang::solana_program::{instruction::Instruction, native_token::LAMPORTS_PER_SOL, program_error::ProgramError};
+use anchor_lang::{Discriminator, Space};
+use solmarket::handlers::EscrowError;
+use solmarket::state::{FeeSplit, Treasury};
+use solmarket_test_runtime::market::Market;
+use solmarket_test_runtime::{instruction, program_error, rent_exempt, with_remaining};
+
+fn treasury_space() -> usize {
+    Treasury::DISCRIMINATOR.len() + Treasury::INIT_SPACE
+}
+
+fn withdraw_fees(m: &Market, signer: Pubkey, destination: Pubkey, amount: u64) -> Instruction {
+    instruction(
+        solmarket::accounts::WithdrawFees {
+            authority: signer,
+            config: m.config,
+            treasury: m.treasury,
+            destination: Some(destination),
+        },
+        solmarket::instruction::WithdrawFees { amount },
+    )
+}
+
+#[test]
+fn a_one_lamport_fee_lands_in_the_treasury_and_can_be_withdrawn() {
+    let mut m = Market::new();
+    let floor = rent_exempt(treasury_space());
+    assert_eq!(m.bank.lamports(&m.treasury), floor);
+
+    // 50 lamports at the default 2% is a 1 lamport fee, far below what a fresh wallet needs for rent
+    let escrow = m.open_escrow(1, 50);
+    let escrow_rent = m.bank.lamports(&escrow) - 50;
+    let payout_before = m.bank.lamports(&m.payout_wallet);
+    let ix = m.release_escrow(1);
+    m.bank.process(&[ix], &[&m.merchant]).unwrap();
+
+    assert_eq!(m.bank.lamports(&m.treasury), floor + 1);
+    let treasury = m.bank.anchor_account::<Treasury>(&m.treasury);
+    assert_eq!(treasury.total_fees, 1);
+    assert_eq!(treasury.escrows_released, 1);
+    assert_eq!(treasury.volume_lamports, 50);
+    // The merchant also gets the escrow's rent back when it closes
+    assert_eq!(m.bank.lamports(&m.payout_wallet), payout_before + 49 + escrow_rent);
+    assert!(m.bank.account(&escrow).is_none());
+
+    let ix = withdraw_fees(&m, m.authority, m.authority, 1);
+    m.bank.process(&[ix], &[&m.authority]).unwrap();
+    assert_eq!(m.bank.lamports(&m.treasury), floor);
+    assert_eq!(m.bank.anchor_account::<Treasury>(&m.treasury).total_withdrawn, 1);
+}
+
+#[test]
+fn withdrawals_stop_at_the_rent_floor() {
+    let mut m = Market::new();
+    let floor = rent_exempt(treasury_space());
+    m.open_escrow(1, 1_000_000);
+    let ix = m.release_escrow(1);
+    m.bank.process(&[ix], &[&m.merchant]).unwrap();
+    let fees = m.bank.lamports(&m.treasury) - floor;
+    assert_eq!(fees, 20_000);
+
+    // One lamport past what was collected would dip into the rent reserve
+    let ix = withdraw_fees(&m, m.authority, m.authority, fees + 1);
+    let err = m.bank.process(&[ix], &[&m.authority]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::InsufficientTreasury));
+
+    let before = m.bank.lamports(&m.authority);
+    let ix = withdraw_fees(&m, m.authority, m.authority, fees);
+    m.bank.process(&[ix], &[&m.authority]).unwrap();
+    assert_eq!(m.bank.lamports(&m.treasury), floor);
+    assert_eq!(m.bank.lamports(&m.authority), before + fees);
+
+    // Empty down to the floor, even a single lamport is refused
+    let ix = withdraw_fees(&m, m.authority, m.authority, 1);
+    let err = m.bank.process(&[ix], &[&m.authority]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::InsufficientTreasury));
+}
+
+#[test]
+fn an_empty_treasury_has_nothing_to_withdraw() {
+    let mut m = Market::new();
+    let ix = withdraw_fees(&m, m.authority, m.authority, 1);
+    let err = m.bank.process(&[ix], &[&m.authority]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::InsufficientTreasury));
+
+    let ix = withdraw_fees(&m, m.authority, m.authority, 0);
+    let err = m.bank.process(&[ix], &[&m.authority]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::InvalidAmount));
+}
+
+#[test]
+fn a_small_withdrawal_cannot_leave_a_fresh_destination_below_rent() {
+    let mut m = Market::new();
+    m.open_escrow(1, 50);
+    let ix = m.release_escrow(1);
+    m.bank.process(&[ix], &[&m.merchant]).unwrap();
+
+    // The runtime refuses to create a rent-paying wallet, so the treasury keeps the fee
+    let fresh = Pubkey::new_unique();
+    let ix = withdraw_fees(&m, m.authority, fresh, 1);
+    let err = m.bank.process(&[ix], &[&m.authority]).unwrap_err();
+    assert_eq!(err, ProgramError::AccountNotRentExempt);
+    assert_eq!(m.bank.anchor_account::<Treasury>(&m.treasury).total_withdrawn, 0);
+}
+
+#[test]
+fn only_the_authority_withdraws() {
+    let mut m = Market::new();
+    m.open_escrow(1, 1_000_000);
+    let ix = m.release_escrow(1);
+    m.bank.process(&[ix], &[&m.merchant]).unwrap();
+
+    let ix = withdraw_fees(&m, m.merchant, m.merchant, 1);
+    let err = m.bank.process(&[ix], &[&m.merchant]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::Unauthorized));
+}
+
+fn set_fee_splits(m: &mut Market, splits: Vec<FeeSplit>) {
+    let ix = instruction(
+        solmarket::accounts::UpdateConfig { authority: m.authority, config: m.config },
+        solmarket::instruction::SetFeeSplits { splits },
+    );
+    m.bank.process(&[ix], &[&m.authority]).unwrap();
+}
+
+// Withdrawal shared across the fee splits, recipients in Config order
+fn withdraw_to_splits(m: &Market, recipients: &[Pubkey], amount: u64) -> Instruction {
+    let ix = instruction(
+        solmarket::accounts::WithdrawFees { authority: m.authority, config: m.config, treasury: m.treasury, destination: None },
+        solmarket::instruction::WithdrawFees { amount },
+    );
+    with_remaining(ix, recipients.iter().map(|recipient| AccountMeta::new(*recipient, false)))
+}
+
+#[test]
+fn split_recipients_are_paid_at_withdrawal_not_release() {
+    let mut m = Market::new();
+    let (ops, partner) = (Pubkey::new_unique(), Pubkey::new_unique());
+    set_fee_splits(
+        &mut m,
+        vec![FeeSplit { recipient: ops, weight_bps: 7_000 }, FeeSplit { recipient: partner, weight_bps: 3_000 }],
+    );
+    let floor = rent_exempt(treasury_space());
+
+    // Neither recipient exists yet; a 1 lamport fee still releases, into the treasury
+    m.open_escrow(1, 50);
+    let ix = m.release_escrow(1);
+    m.bank.process(&[ix], &[&m.merchant]).unwrap();
+    assert_eq!(m.bank.lamports(&m.treasury), floor + 1);
+    assert!(m.bank.account(&ops).is_none());
+
+    m.open_escrow(2, 100 * LAMPORTS_PER_SOL / 2);
+    let ix = m.release_escrow(2);
+    m.bank.process(&[ix], &[&m.merchant]).unwrap();
+    let fees = m.bank.lamports(&m.treasury) - floor;
+    assert_eq!(fees, LAMPORTS_PER_SOL + 1);
+
+    // Shares round down; the dust goes to the first recipient
+    let ix = withdraw_to_splits(&m, &[ops, partner], fees);
+    m.bank.process(&[ix], &[&m.authority]).unwrap();
+    assert_eq!(m.bank.lamports(&ops), 700_000_001);
+    assert_eq!(m.bank.lamports(&partner), 300_000_000);
+    assert_eq!(m.bank.lamports(&m.treasury), floor);
+    assert_eq!(m.bank.anchor_account::<Treasury>(&m.treasury).total_withdrawn, fees);
+}
+
+#[test]
+fn split_withdrawals_need_every_recipient_in_order() {
+    let mut m = Market::new();
+    let (ops, partner) = (Pubkey::new_unique(), Pubkey::new_unique());
+    set_fee_splits(
+        &mut m,
+        vec![FeeSplit { recipient: ops, weight_bps: 5_000 }, FeeSplit { recipient: partner, weight_bps: 5_000 }],
+    );
+    m.open_escrow(1, 100 * LAMPORTS_PER_SOL / 2);
+    let ix = m.release_escrow(1);
+    m.bank.process(&[ix], &[&m.merchant]).unwrap();
+
+    for recipients in [vec![ops], vec![partner, ops]] {
+        let ix = withdraw_to_splits(&m, &recipients, LAMPORTS_PER_SOL);
+        let err = m.bank.process(&[ix], &[&m.authority]).unwrap_err();
+        assert_eq!(err, program_error(EscrowError::InvalidFeeSplits));
+    }
+    // A single destination would bypass the splits
+    let ix = with_remaining(
+        withdraw_fees(&m, m.authority, m.authority, LAMPORTS_PER_SOL),
+        [AccountMeta::new(ops, false), AccountMeta::new(partner, false)],
+    );
+    let err = m.bank.process(&[ix], &[&m.authority]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::InvalidFeeSplits));
+
+    let ix = withdraw_to_splits(&m, &[ops, partner], LAMPORTS_PER_SOL);
+    m.bank.process(&[ix], &[&m.authority]).unwrap();
+    assert_eq!(m.bank.lamports(&ops), LAMPORTS_PER_SOL / 2);
+}
+
+#[test]
+fn withdrawals_without_splits_need_a_destination() {
+    let mut m = Market::new();
+    m.open_escrow(1, 1_000_000);
+    let ix = m.release_escrow(1);
+    m.bank.process(&[ix], &[&m.merchant]).unwrap();
+
+    let ix = withdraw_to_splits(&m, &[], 1);
+    let err = m.bank.process(&[ix], &[&m.authority]).unwrap_err();
+    assert_eq!(err, program_error(EscrowError::DestinationMissing));
+}
@@ -0,0 +1,478 @@
+// In-process stand-in for a validator. Instructions run natively through
+// solmarket::entry against an in-memory account store: accounts are serialized the
+// way the BPF loader lays them out, the syscalls the program uses (clock, rent,
+// return data, CPI and event self-CPIs) are stubbed, and the runtime's
+// post-instruction checks (signers, read-only accounts, balance, rent) are applied.
+// Events the program emits are kept for the test to read back.
+// CPIs reach the system program, stubbed here, and the SPL Token, Token-2022 and
+// associated token account programs, whose own processors run natively; those can
+// be called directly too, e.g. to set up mints.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::sync::Once;
+
+use anchor_lang::event::EVENT_IX_TAG_LE;
+use anchor_lang::prelude::AccountMeta;
+use anchor_lang::solana_program::{
+    account_info::AccountInfo,
+    bpf_loader,
+    clock::Clock,
+    ed25519_program,
+    entrypoint::{deserialize, ProgramResult, MAX_PERMITTED_DATA_INCREASE, NON_DUP_MARKER, SUCCESS},
+    instruction::Instruction,
+    program_error::ProgramError,
+    program_stubs::{set_syscall_stubs, SyscallStubs},
+    program_utils::limited_deserialize,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction::SystemInstruction,
+    system_program,
+    sysvar::{
+        self,
+        instructions::{construct_instructions_data, store_current_index, BorrowedAccountMeta, BorrowedInstruction},
+    },
+};
+use anchor_lang::{AccountDeserialize, AccountSerialize, AnchorDeserialize, Discriminator, InstructionData, ToAccountMetas};
+use anchor_spl::associated_token::spl_associated_token_account;
+use anchor_spl::token::spl_token;
+use anchor_spl::token_2022::spl_token_2022;
+use solana_sdk::{ed25519_instruction, feature_set::FeatureSet, native_loader, signature::Keypair};
+
+pub mod market;
+pub mod token;
+
+thread_local! {
+    // Cluster time seen by the instruction running on this thread
+    static NOW: Cell<i64> = const { Cell::new(0) };
+    // Programs currently executing, innermost last: PDA signatures and return data are theirs
+    static CALL_STACK: RefCell<Vec<Pubkey>> = const { RefCell::new(Vec::new()) };
+    static RETURN_DATA: RefCell<Option<(Pubkey, Vec<u8>)>> = const { RefCell::new(None) };
+    // Events emitted through emit_cpi! by the running transaction, discriminator first
+    static EVENTS: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+}
+
+// The programs instructions can run against besides solmarket
+const BUILTINS: [Pubkey; 4] =
+    [system_program::ID, spl_token::ID, spl_token_2022::ID, spl_associated_token_account::ID];
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TestAccount {
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub owner: Pubkey,
+    pub executable: bool,
+}
+
+pub struct TestBank {
+    accounts: HashMap<Pubkey, TestAccount>,
+    now: i64,
+    events: Vec<Vec<u8>>,
+}
+
+impl TestBank {
+    pub fn new() -> Self {
+        static STUBS: Once = Once::new();
+        STUBS.call_once(|| {
+            set_syscall_stubs(Box::new(Stubs));
+        });
+        let mut bank = Self { accounts: HashMap::new(), now: 1_700_000_000, events: Vec::new() };
+        let program = |owner| TestAccount { lamports: 1, data: vec![], owner, executable: true };
+        bank.set_account(solmarket::ID, program(bpf_loader::ID));
+        bank.set_account(system_program::ID, program(native_loader::ID));
+        for spl in &BUILTINS[1..] {
+            bank.set_account(*spl, program(bpf_loader::ID));
+        }
+        bank
+    }
+
+    pub fn now(&self) -> i64 {
+        self.now
+    }
+
+    pub fn warp_to(&mut self, unix_timestamp: i64) {
+        self.now = unix_timestamp;
+    }
+
+    pub fn account(&self, key: &Pubkey) -> Option<&TestAccount> {
+        self.accounts.get(key)
+    }
+
+    // Every account owned by `owner`, in no particular order, as getProgramAccounts scans them
+    pub fn program_accounts(&self, owner: &Pubkey) -> Vec<(Pubkey, &TestAccount)> {
+        self.accounts.iter().filter(|(_, account)| account.owner == *owner).map(|(key, account)| (*key, account)).collect()
+    }
+
+    pub fn lamports(&self, key: &Pubkey) -> u64 {
+        self.accounts.get(key).map_or(0, |account| account.lamports)
+    }
+
+    pub fn set_account(&mut self, key: Pubkey, account: TestAccount) {
+        self.accounts.insert(key, account);
+    }
+
+    // Credit a wallet, creating it as a system account if needed
+    pub fn airdrop(&mut self, key: &Pubkey, lamports: u64) {
+        self.accounts
+            .entry(*key)
+            .or_insert_with(|| TestAccount { owner: system_program::ID, ..TestAccount::default() })
+            .lamports += lamports;
+    }
+
+    // Write a program account directly, rent-exempt at `space` bytes
+    pub fn set_anchor_account<T: AccountSerialize>(&mut self, key: Pubkey, value: &T, space: usize) {
+        let mut data = Vec::with_capacity(space);
+        value.try_serialize(&mut data).unwrap();
+        assert!(data.len() <= space, "account does not fit in {space} bytes");
+        data.resize(space, 0);
+        self.set_account(
+            key,
+            TestAccount { lamports: rent_exempt(space), data, owner: solmarket::ID, executable: false },
+        );
+    }
+
+    pub fn anchor_account<T: AccountDeserialize>(&self, key: &Pubkey) -> T {
+        let account = self.accounts.get(key).unwrap_or_else(|| panic!("no account at {key}"));
+        T::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    // Run the instructions as one transaction; nothing is written back if any of them fails
+    pub fn process(&mut self, instructions: &[Instruction], signers: &[&Pubkey]) -> Result<(), ProgramError> {
+        let snapshot = self.accounts.clone();
+        EVENTS.with(|events| events.take());
+        let result = (0..instructions.len()).try_for_each(|index| self.execute(instructions, index, signers));
+        self.events = EVENTS.with(|events| events.take());
+        if result.is_err() {
+            self.accounts = snapshot;
+            self.events.clear();
+        }
+        result
+    }
+
+    // Events of type `T` the last successful transaction emitted, in order
+    pub fn events<T: Discriminator + AnchorDeserialize>(&self) -> Vec<T> {
+        self.events
+            .iter()
+            .filter_map(|data| data.strip_prefix(&T::DISCRIMINATOR[..]))
+            .map(|mut data| T::deserialize(&mut data).unwrap())
+            .collect()
+    }
+
+    fn execute(&mut self, instructions: &[Instruction], index: usize, signers: &[&Pubkey]) -> Result<(), ProgramError> {
+        let ix = &instructions[index];
+        if ix.program_id == ed25519_program::ID {
+            let datas: Vec<&[u8]> = instructions.iter().map(|ix| ix.data.as_slice()).collect();
+            return ed25519_instruction::verify(&ix.data, &datas, &FeatureSet::all_enabled())
+                .map_err(|_| ProgramError::InvalidArgument);
+        }
+        assert!(
+            ix.program_id == solmarket::ID || BUILTINS.contains(&ix.program_id),
+            "unsupported program {}",
+            ix.program_id
+        );
+        for meta in ix.accounts.iter().filter(|meta| meta.is_signer) {
+            if !signers.contains(&&meta.pubkey) {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+        }
+
+        // Serialize the accounts the way the loader does; repeated keys become duplicate markers
+        let is_signer = |key: &Pubkey| ix.accounts.iter().any(|meta| meta.pubkey == *key && meta.is_signer);
+        let is_writable = |key: &Pubkey| ix.accounts.iter().any(|meta| meta.pubkey == *key && meta.is_writable);
+        let mut unique: Vec<(Pubkey, TestAccount)> = Vec::new();
+        let mut input = Vec::new();
+        input.extend_from_slice(&(ix.accounts.len() as u64).to_le_bytes());
+        for (i, meta) in ix.accounts.iter().enumerate() {
+            if let Some(position) = ix.accounts[..i].iter().position(|earlier| earlier.pubkey == meta.pubkey) {
+                input.push(position as u8);
+                input.extend_from_slice(&[0u8; 7]);
+                continue;
+            }
+            let account = self.load(&meta.pubkey, instructions, index);
+            input.push(NON_DUP_MARKER);
+            input.push(is_signer(&meta.pubkey) as u8);
+            input.push(is_writable(&meta.pubkey) as u8);
+            input.push(account.executable as u8);
+            input.extend_from_slice(&[0u8; 4]); // original data length, filled in by deserialize
+            input.extend_from_slice(meta.pubkey.as_ref());
+            input.extend_from_slice(account.owner.as_ref());
+            input.extend_from_slice(&account.lamports.to_le_bytes());
+            input.extend_from_slice(&(account.data.len() as u64).to_le_bytes());
+            input.extend_from_slice(&account.data);
+            input.resize(input.len() + MAX_PERMITTED_DATA_INCREASE, 0);
+            input.resize(input.len().next_multiple_of(8), 0);
+            input.extend_from_slice(&u64::MAX.to_le_bytes()); // rent epoch
+            unique.push((meta.pubkey, account));
+        }
+        input.extend_from_slice(&(ix.data.len() as u64).to_le_bytes());
+        input.extend_from_slice(&ix.data);
+        input.extend_from_slice(ix.program_id.as_ref());
+        // The program reads u64s straight out of its input, so it has to be 8-byte aligned
+        let mut aligned = vec![0u64; input.len().div_ceil(8)];
+        unsafe { std::ptr::copy_nonoverlapping(input.as_ptr(), aligned.as_mut_ptr() as *mut u8, input.len()) };
+
+        NOW.with(|now| now.set(self.now));
+        RETURN_DATA.with(|data| data.take());
+        let (program_id, infos, data) = unsafe { deserialize(aligned.as_mut_ptr() as *mut u8) };
+        if *program_id == solmarket::ID {
+            in_program(program_id, || solmarket::entry(program_id, &infos, data))?;
+        } else {
+            process_builtin(program_id, &infos, data)?;
+        }
+
+        let mut post = Vec::with_capacity(unique.len());
+        for (key, pre) in &unique {
+            let info = infos.iter().find(|info| info.key == key).unwrap();
+            let account = TestAccount {
+                lamports: info.lamports(),
+                data: info.data.borrow().to_vec(),
+                owner: *info.owner,
+                executable: info.executable,
+            };
+            if !is_writable(key) && account != *pre {
+                panic!("instruction modified read-only account {key}");
+            }
+            check_rent_state(key, pre, &account)?;
+            post.push((*key, account));
+        }
+        let lamports_before: u128 = unique.iter().map(|(_, account)| account.lamports as u128).sum();
+        let lamports_after: u128 = post.iter().map(|(_, account)| account.lamports as u128).sum();
+        assert_eq!(lamports_before, lamports_after, "instruction created or destroyed lamports");
+
+        for (key, account) in post {
+            if !is_writable(&key) {
+                continue;
+            }
+            // Accounts left with no lamports are garbage collected
+            if account.lamports == 0 {
+                self.accounts.remove(&key);
+            } else {
+                self.accounts.insert(key, account);
+            }
+        }
+        Ok(())
+    }
+
+    fn load(&self, key: &Pubkey, instructions: &[Instruction], index: usize) -> TestAccount {
+        if *key == sysvar::instructions::ID {
+            let borrowed: Vec<BorrowedInstruction> = instructions
+                .iter()
+                .map(|ix| BorrowedInstruction {
+                    program_id: &ix.program_id,
+                    accounts: ix
+                        .accounts
+                        .iter()
+                        .map(|meta| BorrowedAccountMeta {
+                            pubkey: &meta.pubkey,
+                            is_signer: meta.is_signer,
+                            is_writable: meta.is_writable,
+                        })
+                        .collect(),
+                    data: &ix.data,
+                })
+                .collect();
+            let mut data = construct_instructions_data(&borrowed);
+            store_current_index(&mut data, index as u16);
+            return TestAccount { lamports: rent_exempt(data.len()), data, owner: sysvar::ID, executable: false };
+        }
+        self.accounts
+            .get(key)
+            .cloned()
+            .unwrap_or_else(|| TestAccount { owner: system_program::ID, ..TestAccount::default() })
+    }
+}
+
+impl Default for TestBank {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn rent_exempt(data_len: usize) -> u64 {
+    Rent::default().minimum_balance(data_len)
+}
+
+// The error an instruction fails with when the program returns `error`
+pub fn program_error(error: impl Into<anchor_lang::error::Error>) -> ProgramError {
+    error.into().into()
+}
+
+pub fn instruction(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction { program_id: solmarket::ID, accounts: accounts.to_account_metas(None), data: data.data() }
+}
+
+// Same instruction with one more remaining account appended
+pub fn with_remaining(mut ix: Instruction, metas: impl IntoIterator<Item = AccountMeta>) -> Instruction {
+    ix.accounts.extend(metas);
+    ix
+}
+
+pub fn pda(seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, &solmarket::ID).0
+}
+
+pub fn event_authority() -> Pubkey {
+    pda(&[b"__event_authority"])
+}
+
+// Ed25519 program instruction verifying `signer`'s signature over `message`
+pub fn ed25519_verify(signer: &Keypair, message: &[u8]) -> Instruction {
+    let keypair = ed25519_dalek::Keypair::from_bytes(&signer.to_bytes()).unwrap();
+    ed25519_instruction::new_ed25519_instruction(&keypair, message)
+}
+
+// Rent state transitions the runtime allows: an account may end up empty or
+// rent-exempt, or stay rent-paying at the same size without gaining lamports
+fn check_rent_state(key: &Pubkey, pre: &TestAccount, post: &TestAccount) -> ProgramResult {
+    let rent_paying = |account: &TestAccount| account.lamports > 0 && account.lamports < rent_exempt(account.data.len());
+    if !rent_paying(post) || *key == sysvar::instructions::ID {
+        return Ok(());
+    }
+    if rent_paying(pre) && pre.data.len() == post.data.len() && post.lamports <= pre.lamports {
+        return Ok(());
+    }
+    Err(ProgramError::AccountNotRentExempt)
+}
+
+struct Stubs;
+
+impl SyscallStubs for Stubs {
+    fn sol_log(&self, _message: &str) {}
+
+    fn sol_log_data(&self, _fields: &[&[u8]]) {}
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        let clock = Clock { unix_timestamp: NOW.with(Cell::get), ..Clock::default() };
+        unsafe { (var_addr as *mut Clock).write(clock) };
+        SUCCESS
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { (var_addr as *mut Rent).write(Rent::default()) };
+        SUCCESS
+    }
+
+    fn sol_set_return_data(&self, data: &[u8]) {
+        let program_id = CALL_STACK.with(|stack| *stack.borrow().last().unwrap());
+        RETURN_DATA.with(|return_data| *return_data.borrow_mut() = Some((program_id, data.to_vec())));
+    }
+
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        RETURN_DATA.with(|return_data| return_data.borrow().clone())
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        // emit_cpi! calls back into the program with the event; there is nothing to execute
+        if instruction.program_id == solmarket::ID {
+            let event = instruction.data.strip_prefix(&EVENT_IX_TAG_LE[..]).expect("unsupported self-CPI");
+            EVENTS.with(|events| events.borrow_mut().push(event.to_vec()));
+            return Ok(());
+        }
+        let caller = CALL_STACK.with(|stack| *stack.borrow().last().unwrap());
+        let pda_signers: Vec<Pubkey> = signers_seeds
+            .iter()
+            .map(|seeds| Pubkey::create_program_address(seeds, &caller).unwrap())
+            .collect();
+        // The callee gets the accounts in instruction order, with the privileges the
+        // instruction asks for; the caller can only pass on what it holds itself
+        let mut accounts = Vec::with_capacity(instruction.accounts.len());
+        for meta in &instruction.accounts {
+            let info = account_infos
+                .iter()
+                .find(|info| *info.key == meta.pubkey)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+            if meta.is_signer && !info.is_signer && !pda_signers.contains(info.key) {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            if meta.is_writable && !info.is_writable {
+                return Err(ProgramError::InvalidArgument);
+            }
+            let mut info = info.clone();
+            info.is_signer = meta.is_signer;
+            info.is_writable = meta.is_writable;
+            accounts.push(info);
+        }
+        process_builtin(&instruction.program_id, &accounts, &instruction.data)
+    }
+}
+
+// Run `f` as `program_id`, for the CPIs and return data it produces
+fn in_program<T>(program_id: &Pubkey, f: impl FnOnce() -> T) -> T {
+    CALL_STACK.with(|stack| stack.borrow_mut().push(*program_id));
+    let result = f();
+    CALL_STACK.with(|stack| stack.borrow_mut().pop());
+    result
+}
+
+fn process_builtin(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    in_program(program_id, || match *program_id {
+        system_program::ID => process_system(accounts, data),
+        spl_token::ID => spl_token::processor::Processor::process(program_id, accounts, data),
+        spl_token_2022::ID => spl_token_2022::processor::Processor::process(program_id, accounts, data),
+        spl_associated_token_account::ID => {
+            spl_associated_token_account::processor::process_instruction(program_id, accounts, data)
+        }
+        _ => panic!("unsupported program {program_id}"),
+    })
+}
+
+// The system instructions programs use, with the system program's checks
+fn process_system(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let account = |position: usize| accounts.get(position).ok_or(ProgramError::NotEnoughAccountKeys);
+    match limited_deserialize(data, 1232).map_err(|_| ProgramError::InvalidInstructionData)? {
+        SystemInstruction::Transfer { lamports } => transfer(account(0)?, account(1)?, lamports),
+        SystemInstruction::CreateAccount { lamports, space, owner } => {
+            let new_account = account(1)?;
+            signed(new_account)?;
+            // SystemError::AccountAlreadyInUse
+            if new_account.lamports() > 0 || !new_account.data_is_empty() {
+                return Err(ProgramError::Custom(0));
+            }
+            transfer(account(0)?, new_account, lamports)?;
+            new_account.realloc(space as usize, true)?;
+            new_account.assign(&owner);
+            Ok(())
+        }
+        SystemInstruction::Allocate { space } => {
+            let target = account(0)?;
+            signed(target)?;
+            // SystemError::AccountAlreadyInUse
+            if !target.data_is_empty() || *target.owner != system_program::ID {
+                return Err(ProgramError::Custom(0));
+            }
+            target.realloc(space as usize, true)
+        }
+        SystemInstruction::Assign { owner } => {
+            signed(account(0)?)?;
+            account(0)?.assign(&owner);
+            Ok(())
+        }
+        other => panic!("unsupported system instruction {other:?}"),
+    }
+}
+
+fn signed(info: &AccountInfo) -> ProgramResult {
+    if info.is_signer {
+        Ok(())
+    } else {
+        Err(ProgramError::MissingRequiredSignature)
+    }
+}
+
+fn transfer(from: &AccountInfo, to: &AccountInfo, lamports: u64) -> ProgramResult {
+    signed(from)?;
+    // The system program only moves lamports out of plain wallets
+    if !from.data_is_empty() || *from.owner != system_program::ID {
+        return Err(ProgramError::InvalidArgument);
+    }
+    // SystemError::ResultWithNegativeLamports
+    let remaining = from.lamports().checked_sub(lamports).ok_or(ProgramError::Custom(1))?;
+    **from.try_borrow_mut_lamports()? = remaining;
+    **to.try_borrow_mut_lamports()? += lamports;
+    Ok(())
+}
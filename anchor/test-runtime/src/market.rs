@@ -0,0 +1,248 @@
+// A configured marketplace to run escrow flows against: Config, treasury, one
+// registered merchant and one buyer, all funded
+use anchor_lang::solana_program::{instruction::Instruction, native_token::LAMPORTS_PER_SOL, system_program};
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::InstructionData;
+use solmarket::state::SolEscrow;
+
+use crate::{event_authority, instruction, pda, TestBank};
+
+pub const DAY: i64 = 86_400;
+
+pub struct Market {
+    pub bank: TestBank,
+    pub authority: Pubkey,
+    pub config: Pubkey,
+    pub treasury: Pubkey,
+    pub merchant: Pubkey,
+    pub merchant_account: Pubkey,
+    pub payout_wallet: Pubkey,
+    pub buyer: Pubkey,
+}
+
+impl Market {
+    pub fn new() -> Self {
+        let mut bank = TestBank::new();
+        let authority = Pubkey::new_unique();
+        let merchant = Pubkey::new_unique();
+        let payout_wallet = Pubkey::new_unique();
+        let buyer = Pubkey::new_unique();
+        for wallet in [authority, merchant, payout_wallet, buyer] {
+            bank.airdrop(&wallet, 100 * LAMPORTS_PER_SOL);
+        }
+        let market = Self {
+            bank,
+            authority,
+            config: pda(&[b"config"]),
+            treasury: pda(&[b"treasury"]),
+            merchant,
+            merchant_account: pda(&[b"merchant", merchant.as_ref()]),
+            payout_wallet,
+            buyer,
+        };
+        market.setup()
+    }
+
+    fn setup(mut self) -> Self {
+        let config = instruction(
+            solmarket::accounts::InitializeConfig {
+                authority: self.authority,
+                system_program: system_program::ID,
+                config: self.config,
+                fee_recipient: self.authority,
+            },
+            solmarket::instruction::InitializeConfig {},
+        );
+        let treasury = instruction(
+            solmarket::accounts::InitializeTreasury {
+                authority: self.authority,
+                system_program: system_program::ID,
+                config: self.config,
+                treasury: self.treasury,
+            },
+            solmarket::instruction::InitializeTreasury {},
+        );
+        self.bank.process(&[config, treasury], &[&self.authority]).unwrap();
+        let (merchant, payout_wallet) = (self.merchant, self.payout_wallet);
+        self.register_merchant(merchant, payout_wallet);
+        self
+    }
+
+    // Register `wallet` as a merchant paid out to `payout_wallet`, funding the wallet first
+    pub fn register_merchant(&mut self, wallet: Pubkey, payout_wallet: Pubkey) -> Pubkey {
+        let merchant = pda(&[b"merchant", wallet.as_ref()]);
+        self.bank.airdrop(&wallet, LAMPORTS_PER_SOL);
+        let register = instruction(
+            solmarket::accounts::RegisterMerchant { wallet, system_program: system_program::ID, payout_wallet, merchant },
+            solmarket::instruction::RegisterMerchant {},
+        );
+        self.bank.process(&[register], &[&wallet]).unwrap();
+        merchant
+    }
+
+    // Run one of the authority's UpdateConfig setters, e.g. SetMinBondBps
+    pub fn configure(&mut self, setter: impl InstructionData) {
+        let ix = instruction(solmarket::accounts::UpdateConfig { authority: self.authority, config: self.config }, setter);
+        self.bank.process(&[ix], &[&self.authority]).unwrap();
+    }
+
+    // Replace Config.paused with `paused`, a set of PAUSE_* bits
+    pub fn set_paused(&mut self, paused: u8) {
+        self.configure(solmarket::instruction::SetPaused { paused });
+    }
+
+    pub fn escrow_address(&self, order_id: u64) -> Pubkey {
+        pda(&[b"sol-escrow", self.buyer.as_ref(), &order_id.to_le_bytes()])
+    }
+
+    pub fn initialize_accounts(&self, order_id: u64) -> solmarket::accounts::InitializeEscrow {
+        solmarket::accounts::InitializeEscrow {
+            payer: self.buyer,
+            system_program: system_program::ID,
+            config: self.config,
+            buyer: self.buyer,
+            merchant: self.merchant_account,
+            instructions: None,
+            referrer: None,
+            bond: None,
+            intent: None,
+            escrow: self.escrow_address(order_id),
+            event_authority: event_authority(),
+            program: solmarket::ID,
+        }
+    }
+
+    // A plain order: no milestones, signed intent or hash lock
+    pub fn escrow_args(&self, order_id: u64, amount_lamports: u64, expires_at: i64) -> solmarket::instruction::InitializeEscrow {
+        solmarket::instruction::InitializeEscrow {
+            order_id,
+            amount_lamports,
+            expires_at,
+            milestones: vec![],
+            intent_nonce: 0,
+            hash_lock: [0u8; 32],
+        }
+    }
+
+    pub fn initialize_escrow(&self, order_id: u64, amount_lamports: u64, expires_at: i64) -> Instruction {
+        instruction(self.initialize_accounts(order_id), self.escrow_args(order_id, amount_lamports, expires_at))
+    }
+
+    pub fn fund_escrow(&self, order_id: u64) -> Instruction {
+        instruction(
+            solmarket::accounts::FundEscrow {
+                buyer: self.buyer,
+                system_program: system_program::ID,
+                config: self.config,
+                buyer_stats: None,
+                escrow: self.escrow_address(order_id),
+                event_authority: event_authority(),
+                program: solmarket::ID,
+            },
+            solmarket::instruction::FundEscrow {},
+        )
+    }
+
+    // Create and fund an escrow that expires in a day
+    pub fn open_escrow(&mut self, order_id: u64, amount_lamports: u64) -> Pubkey {
+        let expires_at = self.bank.now() + DAY;
+        let ixs = [self.initialize_escrow(order_id, amount_lamports, expires_at), self.fund_escrow(order_id)];
+        self.bank.process(&ixs, &[&self.buyer]).unwrap();
+        self.escrow_address(order_id)
+    }
+
+    pub fn release_accounts(&self, order_id: u64, signer: Pubkey) -> solmarket::accounts::ReleaseEscrow {
+        solmarket::accounts::ReleaseEscrow {
+            authority: signer,
+            system_program: system_program::ID,
+            config: self.config,
+            merchant_account: self.merchant_account,
+            payout_wallet: self.payout_wallet,
+            treasury: self.treasury,
+            referrer: None,
+            instructions: None,
+            buyer_stats: None,
+            escrow: self.escrow_address(order_id),
+            event_authority: event_authority(),
+            program: solmarket::ID,
+        }
+    }
+
+    pub fn release_escrow(&self, order_id: u64) -> Instruction {
+        instruction(self.release_accounts(order_id, self.merchant), solmarket::instruction::ReleaseEscrow {})
+    }
+
+    pub fn mark_shipped(&self, order_id: u64) -> Instruction {
+        instruction(
+            solmarket::accounts::MarkShipped {
+                merchant: self.merchant,
+                escrow: self.escrow_address(order_id),
+                event_authority: event_authority(),
+                program: solmarket::ID,
+            },
+            solmarket::instruction::MarkShipped { fulfilment_hash: [7u8; 32] },
+        )
+    }
+
+    pub fn refund_accounts(&self, order_id: u64, signer: Pubkey) -> solmarket::accounts::RefundEscrow {
+        solmarket::accounts::RefundEscrow {
+            authority: signer,
+            system_program: system_program::ID,
+            config: self.config,
+            buyer_account: self.buyer,
+            buyer_stats: None,
+            escrow: self.escrow_address(order_id),
+            event_authority: event_authority(),
+            program: solmarket::ID,
+        }
+    }
+
+    pub fn refund_escrow(&self, order_id: u64, signer: Pubkey) -> Instruction {
+        instruction(self.refund_accounts(order_id, signer), solmarket::instruction::RefundEscrow {})
+    }
+
+    // The dispute record of the escrow currently at `order_id`'s address
+    pub fn dispute_address(&self, order_id: u64) -> Pubkey {
+        let escrow = self.escrow_address(order_id);
+        let created_at = self.bank.anchor_account::<SolEscrow>(&escrow).created_at;
+        pda(&[b"dispute", escrow.as_ref(), &created_at.to_le_bytes()])
+    }
+
+    pub fn raise_dispute(&self, order_id: u64, signer: Pubkey) -> Instruction {
+        instruction(
+            solmarket::accounts::RaiseDispute {
+                authority: signer,
+                system_program: system_program::ID,
+                config: self.config,
+                escrow: self.escrow_address(order_id),
+                dispute: self.dispute_address(order_id),
+                event_authority: event_authority(),
+                program: solmarket::ID,
+            },
+            solmarket::instruction::RaiseDispute {},
+        )
+    }
+
+    pub fn mutual_cancel_accounts(&self, order_id: u64, dispute: Option<Pubkey>) -> solmarket::accounts::MutualCancel {
+        solmarket::accounts::MutualCancel {
+            buyer: self.buyer,
+            merchant: self.merchant,
+            payer: self.buyer,
+            buyer_stats: None,
+            escrow: self.escrow_address(order_id),
+            dispute,
+            event_authority: event_authority(),
+            program: solmarket::ID,
+        }
+    }
+
+    pub fn mutual_cancel(&self, order_id: u64, dispute: Option<Pubkey>) -> Instruction {
+        instruction(self.mutual_cancel_accounts(order_id, dispute), solmarket::instruction::MutualCancel {})
+    }
+}
+
+impl Default for Market {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,52 @@
+// Mints and token accounts for token escrows, set up through the SPL programs
+// themselves so they look exactly like what the program will meet on chain
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::system_instruction;
+use anchor_spl::associated_token::{
+    get_associated_token_address_with_program_id, spl_associated_token_account::instruction::create_associated_token_account_idempotent,
+};
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{transfer_fee, ExtensionType, StateWithExtensions},
+    state::{Account, Mint},
+};
+
+use crate::{rent_exempt, TestBank};
+
+pub const DECIMALS: u8 = 6;
+
+// A new mint under `token_program`, minted by `authority`; Token-2022 mints can carry a transfer fee
+pub fn create_mint(bank: &mut TestBank, authority: &Pubkey, token_program: &Pubkey, transfer_fee_bps: Option<u16>) -> Pubkey {
+    let mint = Pubkey::new_unique();
+    let extensions: &[ExtensionType] = if transfer_fee_bps.is_some() { &[ExtensionType::TransferFeeConfig] } else { &[] };
+    let space = ExtensionType::try_calculate_account_len::<Mint>(extensions).unwrap();
+    let mut ixs = vec![system_instruction::create_account(authority, &mint, rent_exempt(space), space as u64, token_program)];
+    if let Some(bps) = transfer_fee_bps {
+        let fee_authority = Some(authority);
+        ixs.push(
+            transfer_fee::instruction::initialize_transfer_fee_config(token_program, &mint, fee_authority, fee_authority, bps, u64::MAX)
+                .unwrap(),
+        );
+    }
+    ixs.push(spl_token_2022::instruction::initialize_mint2(token_program, &mint, authority, None, DECIMALS).unwrap());
+    bank.process(&ixs, &[authority, &mint]).unwrap();
+    mint
+}
+
+// `owner`'s associated token account for `mint`, created by `payer` if it does not exist yet
+pub fn create_token_account(bank: &mut TestBank, payer: &Pubkey, owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    let ix = create_associated_token_account_idempotent(payer, owner, mint, token_program);
+    bank.process(&[ix], &[payer]).unwrap();
+    get_associated_token_address_with_program_id(owner, mint, token_program)
+}
+
+pub fn mint_to(bank: &mut TestBank, authority: &Pubkey, mint: &Pubkey, account: &Pubkey, token_program: &Pubkey, amount: u64) {
+    let ix = spl_token_2022::instruction::mint_to(token_program, mint, account, authority, &[], amount).unwrap();
+    bank.process(&[ix], &[authority]).unwrap();
+}
+
+// Balance of a token account under either token program
+pub fn token_balance(bank: &TestBank, account: &Pubkey) -> u64 {
+    let data = &bank.account(account).unwrap_or_else(|| panic!("no token account at {account}")).data;
+    StateWithExtensions::<Account>::unpack(data).unwrap().base.amount
+}